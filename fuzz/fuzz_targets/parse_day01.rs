@@ -0,0 +1,8 @@
+#![no_main]
+
+use aoc2025::days::day01;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    day01::fuzz_parse(input);
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use aoc2025::days::day02;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    day02::fuzz_parse(input);
+});
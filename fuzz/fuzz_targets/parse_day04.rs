@@ -0,0 +1,8 @@
+#![no_main]
+
+use aoc2025::days::day04;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    day04::fuzz_parse(input);
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use aoc2025::days::day05;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    day05::fuzz_parse(input);
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use aoc2025::days::day06;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    day06::fuzz_parse(input);
+});
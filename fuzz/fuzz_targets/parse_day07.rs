@@ -0,0 +1,8 @@
+#![no_main]
+
+use aoc2025::days::day07;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    day07::fuzz_parse(input);
+});
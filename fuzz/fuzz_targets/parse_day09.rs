@@ -0,0 +1,8 @@
+#![no_main]
+
+use aoc2025::days::day09;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    day09::fuzz_parse(input);
+});
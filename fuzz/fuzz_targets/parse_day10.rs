@@ -0,0 +1,8 @@
+#![no_main]
+
+use aoc2025::days::day10;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    day10::fuzz_parse(input);
+});
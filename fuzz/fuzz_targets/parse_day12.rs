@@ -0,0 +1,8 @@
+#![no_main]
+
+use aoc2025::days::day12;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    day12::fuzz_parse(input);
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use aoc2025::days;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    let solution = days::get_solution(4).unwrap();
+    let _ = solution.part1(input);
+    let _ = solution.part2(input);
+});
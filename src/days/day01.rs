@@ -2,22 +2,210 @@ use crate::Solution;
 use nom::{
     IResult, Parser,
     branch::alt,
-    character::complete::{char, u32 as parse_u32},
-    combinator::map,
+    character::complete::{char, u64 as parse_u64},
+    combinator::{all_consuming, map},
 };
+use rand::{Rng, rngs::StdRng};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use tracing::instrument;
 
 pub struct Day01;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Direction {
+pub enum Direction {
     Left,
     Right,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Instruction {
-    direction: Direction,
-    distance: u32,
+pub struct Instruction {
+    pub direction: Direction,
+    pub distance: u64,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.direction {
+            Direction::Left => write!(f, "L{}", self.distance),
+            Direction::Right => write!(f, "R{}", self.distance),
+        }
+    }
+}
+
+/// A circular dial with positions `0..modulus` and a set of target positions.
+///
+/// Rotating right counts up, rotating left counts down, and both wrap around.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dial {
+    modulus: u64,
+    position: u64,
+    targets: Vec<u64>,
+}
+
+impl Default for Dial {
+    /// The puzzle's dial: 100 positions, starting at 50, counting zeros.
+    fn default() -> Self {
+        Self {
+            modulus: 100,
+            position: 50,
+            targets: vec![0],
+        }
+    }
+}
+
+impl Dial {
+    pub fn new(modulus: u64, start: u64, targets: &[u64]) -> anyhow::Result<Self> {
+        anyhow::ensure!(modulus > 0, "Dial must have at least one position");
+        anyhow::ensure!(
+            start < modulus,
+            "Start position {} is not on a dial of {} positions",
+            start,
+            modulus
+        );
+        if let Some(target) = targets.iter().find(|&&t| t >= modulus) {
+            anyhow::bail!(
+                "Target {} is not on a dial of {} positions",
+                target,
+                modulus
+            );
+        }
+        let mut targets = targets.to_vec();
+        targets.sort_unstable();
+        targets.dedup();
+        Ok(Self {
+            modulus,
+            position: start,
+            targets,
+        })
+    }
+
+    pub fn modulus(&self) -> u64 {
+        self.modulus
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn targets(&self) -> &[u64] {
+        &self.targets
+    }
+
+    pub fn on_target(&self) -> bool {
+        self.targets.binary_search(&self.position).is_ok()
+    }
+
+    /// Rotates the dial and returns how many clicks ended on a target,
+    /// including the final one.
+    pub fn rotate(&mut self, direction: Direction, distance: u64) -> u64 {
+        let hits = self
+            .targets
+            .iter()
+            .map(|&target| {
+                let first = self.clicks_to(target, direction);
+                if distance >= first {
+                    (distance - first) / self.modulus + 1
+                } else {
+                    0
+                }
+            })
+            .sum();
+
+        // u128 so that positions close to u64::MAX cannot overflow
+        let modulus = self.modulus as u128;
+        let position = self.position as u128;
+        let step = (distance % self.modulus) as u128;
+        self.position = match direction {
+            Direction::Right => (position + step) % modulus,
+            Direction::Left => (position + modulus - step) % modulus,
+        } as u64;
+        hits
+    }
+
+    // Clicks needed to reach `target` in `direction`, between 1 and `modulus`
+    fn clicks_to(&self, target: u64, direction: Direction) -> u64 {
+        let (from, to) = match direction {
+            Direction::Right => (self.position as u128, target as u128),
+            Direction::Left => (target as u128, self.position as u128),
+        };
+        let modulus = self.modulus as u128;
+        match (to + modulus - from) % modulus {
+            0 => self.modulus,
+            clicks => clicks as u64,
+        }
+    }
+}
+
+/// Number of rotations that end on a target ("lands on", part 1).
+pub fn count_landings(mut dial: Dial, instructions: impl IntoIterator<Item = Instruction>) -> u64 {
+    let mut landings = 0;
+    for inst in instructions {
+        dial.rotate(inst.direction, inst.distance);
+        if dial.on_target() {
+            landings += 1;
+        }
+    }
+    landings
+}
+
+/// Number of clicks that end on a target ("passes through", part 2).
+///
+/// A single rotation passes at most `u64::MAX` times, so the total is kept in
+/// a u128 that no realistic number of instructions can overflow.
+pub fn count_passes(mut dial: Dial, instructions: impl IntoIterator<Item = Instruction>) -> u128 {
+    instructions
+        .into_iter()
+        .map(|inst| dial.rotate(inst.direction, inst.distance) as u128)
+        .sum()
+}
+
+/// Limit on the instructions [`find_instructions`] adds for whole turns.
+pub const MAX_EXTRA_INSTRUCTIONS: u64 = 1 << 20;
+
+/// Shortest instruction sequence after which `dial` has landed on a target
+/// exactly `landings` times and clicked onto one exactly `passes` times.
+/// `None` leaves that count free.
+///
+/// When the whole turns needed for `passes` do not fit in u64 distances, they
+/// get instructions of their own, which makes the sequence longer than the
+/// shortest one. Fails if that would take more than [`MAX_EXTRA_INSTRUCTIONS`]
+/// instructions, or if the dial never stops off a target where they could go.
+///
+/// Every sequence is run through [`count_landings`] and [`count_passes`]
+/// before it is returned.
+pub fn find_instructions(
+    dial: &Dial,
+    landings: Option<u64>,
+    passes: Option<u64>,
+) -> anyhow::Result<Vec<Instruction>> {
+    let impossible = || {
+        anyhow::anyhow!(
+            "No instruction sequence lands {} times and passes {} times",
+            landings.map_or("any number of".to_string(), |l| l.to_string()),
+            passes.map_or("any number of".to_string(), |p| p.to_string())
+        )
+    };
+    let instructions = if dial.targets.is_empty() {
+        anyhow::ensure!(
+            landings.unwrap_or(0) == 0 && passes.unwrap_or(0) == 0,
+            impossible()
+        );
+        Vec::new()
+    } else {
+        let moves = inverse::shortest_moves(dial, landings, passes).ok_or_else(impossible)?;
+        inverse::to_instructions(dial, &moves, landings.is_some(), passes)?
+    };
+
+    let landed = count_landings(dial.clone(), instructions.iter().copied());
+    let passed = count_passes(dial.clone(), instructions.iter().copied());
+    anyhow::ensure!(
+        landings.is_none_or(|l| l == landed) && passes.is_none_or(|p| p as u128 == passed),
+        "Generated instructions land {} times and pass {} times",
+        landed,
+        passed
+    );
+    Ok(instructions)
 }
 
 fn parse_direction(input: &str) -> IResult<&str, Direction> {
@@ -30,7 +218,7 @@ fn parse_direction(input: &str) -> IResult<&str, Direction> {
 
 fn parse_instruction(input: &str) -> IResult<&str, Instruction> {
     let (input, direction) = parse_direction(input)?;
-    let (input, distance) = parse_u32(input)?;
+    let (input, distance) = parse_u64(input)?;
     Ok((
         input,
         Instruction {
@@ -40,74 +228,846 @@ fn parse_instruction(input: &str) -> IResult<&str, Instruction> {
     ))
 }
 
-fn parse_input(input: &str) -> Vec<Instruction> {
-    input
-        .lines()
-        .filter(|line| !line.is_empty())
-        .map(|line| {
-            parse_instruction(line)
-                .expect("Failed to parse instruction")
-                .1
-        })
-        .collect()
+/// State of the dial after one instruction, see [`trace`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceStep {
+    pub instruction: Instruction,
+    pub before: u64,
+    pub after: u64,
+    /// Clicks of this rotation that ended on a target.
+    pub passes: u64,
+    pub total_landings: u64,
+    pub total_passes: u128,
+}
+
+/// Runs the instructions on the dial, yielding one step per instruction.
+pub fn trace(
+    mut dial: Dial,
+    instructions: impl IntoIterator<Item = Instruction>,
+) -> impl Iterator<Item = TraceStep> {
+    let mut total_landings = 0;
+    let mut total_passes = 0;
+    instructions.into_iter().map(move |instruction| {
+        let before = dial.position();
+        let passes = dial.rotate(instruction.direction, instruction.distance);
+        total_passes += passes as u128;
+        if dial.on_target() {
+            total_landings += 1;
+        }
+        TraceStep {
+            instruction,
+            before,
+            after: dial.position(),
+            passes,
+            total_landings,
+            total_passes,
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    Table,
+    Csv,
+}
+
+const TRACE_COLUMNS: [&str; 7] = [
+    "step",
+    "instruction",
+    "before",
+    "after",
+    "passes",
+    "total_landings",
+    "total_passes",
+];
+
+/// Writes a trace as an aligned table or as CSV with a header row.
+pub fn write_trace(
+    out: &mut impl Write,
+    steps: impl IntoIterator<Item = TraceStep>,
+    format: TraceFormat,
+) -> io::Result<()> {
+    match format {
+        TraceFormat::Table => writeln!(
+            out,
+            "{:>6} {:>11} {:>8} {:>8} {:>8} {:>14} {:>12}",
+            TRACE_COLUMNS[0],
+            TRACE_COLUMNS[1],
+            TRACE_COLUMNS[2],
+            TRACE_COLUMNS[3],
+            TRACE_COLUMNS[4],
+            TRACE_COLUMNS[5],
+            TRACE_COLUMNS[6]
+        )?,
+        TraceFormat::Csv => writeln!(out, "{}", TRACE_COLUMNS.join(","))?,
+    }
+    for (i, step) in steps.into_iter().enumerate() {
+        match format {
+            TraceFormat::Table => writeln!(
+                out,
+                "{:>6} {:>11} {:>8} {:>8} {:>8} {:>14} {:>12}",
+                i + 1,
+                step.instruction.to_string(),
+                step.before,
+                step.after,
+                step.passes,
+                step.total_landings,
+                step.total_passes
+            )?,
+            TraceFormat::Csv => writeln!(
+                out,
+                "{},{},{},{},{},{},{}",
+                i + 1,
+                step.instruction,
+                step.before,
+                step.after,
+                step.passes,
+                step.total_landings,
+                step.total_passes
+            )?,
+        }
+    }
+    Ok(())
+}
+
+/// Iterator over the instructions of a reader, one line at a time.
+///
+/// Blank lines are skipped; a bad line is reported with its line number.
+pub struct Instructions<R> {
+    reader: R,
+    line: String,
+    line_number: usize,
+}
+
+impl<R: BufRead> Iterator for Instructions<R> {
+    type Item = anyhow::Result<Instruction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => self.line_number += 1,
+                Err(e) => {
+                    return Some(Err(anyhow::anyhow!(
+                        "Failed to read line {}: {}",
+                        self.line_number + 1,
+                        e
+                    )));
+                }
+            }
+            let line = self.line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            return Some(
+                all_consuming(parse_instruction)
+                    .parse(line)
+                    .map(|(_, inst)| inst)
+                    .map_err(|e| {
+                        anyhow::anyhow!("Failed to parse line {}: {}", self.line_number, e)
+                    }),
+            );
+        }
+    }
+}
+
+pub fn read_instructions<R: BufRead>(reader: R) -> Instructions<R> {
+    Instructions {
+        reader,
+        line: String::new(),
+        line_number: 0,
+    }
+}
+
+/// Hands the instructions of `reader` to `f` as they are read, in constant
+/// memory. Stops at the first bad line and returns its error instead.
+pub fn with_instructions<T>(
+    reader: impl BufRead,
+    f: impl FnOnce(&mut dyn Iterator<Item = Instruction>) -> T,
+) -> anyhow::Result<T> {
+    let mut error = None;
+    let mut instructions =
+        read_instructions(reader).map_while(|inst| inst.map_err(|e| error = Some(e)).ok());
+    let result = f(&mut instructions);
+    match error {
+        Some(e) => Err(e),
+        None => Ok(result),
+    }
+}
+
+#[instrument(name = "parse", level = "debug", skip_all)]
+pub fn parse_input(input: &str) -> anyhow::Result<Vec<Instruction>> {
+    read_instructions(input.as_bytes()).collect()
+}
+
+/// Entry point for the `parse_day01` fuzz target.
+#[cfg(fuzzing)]
+pub fn fuzz_parse(input: &str) {
+    let _ = parse_input(input);
+}
+
+/// Generates `size` random rotations.
+pub fn generate(size: usize, rng: &mut StdRng) -> String {
+    let mut out = String::new();
+    for _ in 0..size {
+        let direction = if rng.random_bool(0.5) { 'L' } else { 'R' };
+        out.push_str(&format!("{}{}\n", direction, rng.random_range(1..1000)));
+    }
+    out
 }
 
 impl Solution for Day01 {
     fn part1(&self, input: &str) -> anyhow::Result<String> {
-        let instructions = parse_input(input);
-        let mut x = 50;
-        let mut zeros = 0;
-        for inst in &instructions {
-            match inst.direction {
-                Direction::Left => x -= inst.distance as i32,
-                Direction::Right => x += inst.distance as i32,
+        self.part1_from(&mut input.as_bytes())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        self.part2_from(&mut input.as_bytes())
+    }
+
+    fn part1_from(&self, reader: &mut dyn BufRead) -> anyhow::Result<String> {
+        let zeros = with_instructions(reader, |instructions| {
+            count_landings(Dial::default(), instructions)
+        })?;
+        Ok(format!("Points at 0 {} times", zeros))
+    }
+
+    fn part2_from(&self, reader: &mut dyn BufRead) -> anyhow::Result<String> {
+        let zeros = with_instructions(reader, |instructions| {
+            count_passes(Dial::default(), instructions)
+        })?;
+        Ok(format!("Pass by 0 {} times", zeros))
+    }
+}
+
+// Search behind `find_instructions`.
+//
+// Between two consecutive targets, every position sees the same sequence of
+// targets in both directions, so the search only tracks which target the dial
+// is on, or which gap between targets it is in. Going one more time around
+// the dial passes every target once more and ends in the same place, so passes
+// only matter modulo the number of targets: each move takes the fewest passes
+// that reach its destination, and the surplus is added as whole turns at the
+// end.
+mod inverse {
+    use super::{Dial, Direction, Instruction, MAX_EXTRA_INSTRUCTIONS};
+    use std::collections::HashMap;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Spot {
+        Target(usize),
+        // Positions strictly between target `i` and the next one to the right
+        Gap(usize),
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Move {
+        direction: Direction,
+        hits: u64,
+        land: bool,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    struct State {
+        spot: Spot,
+        landings: u64,
+        residue: u64,
+    }
+
+    // Passes so far and the step that reached each state
+    type Layer = HashMap<State, (u64, Option<(State, Move)>)>;
+
+    fn start_spot(dial: &Dial) -> Spot {
+        let targets = &dial.targets;
+        match targets.binary_search(&dial.position) {
+            Ok(i) => Spot::Target(i),
+            Err(i) => Spot::Gap((i + targets.len() - 1) % targets.len()),
+        }
+    }
+
+    fn gap_is_empty(dial: &Dial, i: usize) -> bool {
+        let targets = &dial.targets;
+        let next = targets[(i + 1) % targets.len()];
+        let width =
+            (next as u128 + dial.modulus as u128 - targets[i] as u128) % dial.modulus as u128;
+        // A single target leaves a gap all the way around
+        width == 1 || (width == 0 && dial.modulus == 1)
+    }
+
+    // Every move from `spot` with the fewest hits reaching its destination
+    fn moves_from(dial: &Dial, spot: Spot) -> Vec<(Move, Spot)> {
+        let k = dial.targets.len();
+        let mut moves = Vec::new();
+        if let Spot::Target(i) = spot {
+            let stay = Move {
+                direction: Direction::Right,
+                hits: 0,
+                land: true,
+            };
+            moves.push((stay, Spot::Target(i)));
+        }
+        for direction in [Direction::Right, Direction::Left] {
+            for hits in 0..=k {
+                // Index of the target hit last, and of the gap just past it
+                let (target, gap) = match (direction, spot) {
+                    (Direction::Right, Spot::Target(i) | Spot::Gap(i)) => {
+                        ((i + hits) % k, (i + hits) % k)
+                    }
+                    (Direction::Left, Spot::Target(i)) => {
+                        ((i + k - hits) % k, (i + 2 * k - hits - 1) % k)
+                    }
+                    (Direction::Left, Spot::Gap(i)) => ((i + 1 + k - hits) % k, (i + k - hits) % k),
+                };
+                let hits = hits as u64;
+                if hits > 0 {
+                    let land = Move {
+                        direction,
+                        hits,
+                        land: true,
+                    };
+                    moves.push((land, Spot::Target(target)));
+                }
+                // Stopping after no hits or after a whole turn ends in the same gap,
+                // but only one of them moves away from where the dial is
+                let useful = match spot {
+                    Spot::Target(_) => hits < k as u64,
+                    Spot::Gap(_) => hits > 0,
+                };
+                if useful && !gap_is_empty(dial, gap) {
+                    let stop = Move {
+                        direction,
+                        hits,
+                        land: false,
+                    };
+                    moves.push((stop, Spot::Gap(gap)));
+                }
+            }
+        }
+        moves
+    }
+
+    /// Fewest moves reaching the wanted counts, with the passes of each move
+    /// reduced modulo the number of targets.
+    ///
+    /// Layer `n` holds the states reached by `n` moves with fewer passes than
+    /// any shorter sequence reached them with; other states cannot lead to a
+    /// shorter answer. The search ends when a layer is empty.
+    pub fn shortest_moves(
+        dial: &Dial,
+        landings: Option<u64>,
+        passes: Option<u64>,
+    ) -> Option<Vec<Move>> {
+        let k = dial.targets.len() as u64;
+        let residues = if passes.is_some() { k } else { 1 };
+        let moves: HashMap<Spot, Vec<(Move, Spot)>> = (0..k as usize)
+            .flat_map(|i| [Spot::Target(i), Spot::Gap(i)])
+            .map(|spot| (spot, moves_from(dial, spot)))
+            .collect();
+        let is_goal = |state: &State| {
+            landings.is_none_or(|l| state.landings == l)
+                && passes.is_none_or(|p| state.residue == p % k)
+        };
+
+        let start = State {
+            spot: start_spot(dial),
+            landings: 0,
+            residue: 0,
+        };
+        // The start is left out: it has no move to add whole turns to, so it
+        // does not make coming back to it pointless
+        let mut best: HashMap<State, u64> = HashMap::new();
+        let mut layers: Vec<Layer> = vec![HashMap::from([(start, (0, None))])];
+        loop {
+            let layer = layers.last().unwrap();
+            // Whole turns need at least one move to go into
+            let can_turn = layers.len() > 1;
+            if let Some((&state, _)) = layer
+                .iter()
+                .filter(|&(s, &(sum, _))| {
+                    is_goal(s) && (can_turn || passes.is_none_or(|p| p == sum))
+                })
+                .min_by_key(|(_, (sum, _))| *sum)
+            {
+                return Some(path_to(&layers, state));
+            }
+            let mut next = Layer::new();
+            for (&state, &(sum, _)) in layer {
+                for &(mv, spot) in &moves[&state.spot] {
+                    let sum = sum + mv.hits;
+                    let landed = state.landings + (mv.land && landings.is_some()) as u64;
+                    if passes.is_some_and(|p| sum > p) || landings.is_some_and(|l| landed > l) {
+                        continue;
+                    }
+                    let to = State {
+                        spot,
+                        landings: landed,
+                        residue: (state.residue + mv.hits) % residues,
+                    };
+                    if best.get(&to).is_none_or(|&b| sum < b) {
+                        best.insert(to, sum);
+                        next.insert(to, (sum, Some((state, mv))));
+                    }
+                }
             }
-            x %= 100;
-            if x == 0 {
-                zeros += 1;
+            if next.is_empty() {
+                return None;
             }
+            layers.push(next);
         }
-        Ok(format!("Points at 0 {} times", zeros))
     }
 
-    fn part2(&self, input: &str) -> anyhow::Result<String> {
-        let instructions = parse_input(input);
-        let mut x = 50i32;
-        let mut zeros = 0;
-        for inst in &instructions {
-            // If we go right, starting from 34.
-            // We go by zero >= 1 time  if dist >= 66
-            // We go by zero >= 2 times if dist >= 166
-
-            // If we go left, starting from 34
-            // We go by zero >= 1 time  if dist >= 34
-            // We go by zero >= 2 times if dist >= 134
-            match inst {
-                &Instruction {
-                    direction: Direction::Right,
-                    distance,
-                } => {
-                    zeros += (x + distance as i32) / 100;
-                    x += distance as i32;
+    fn path_to(layers: &[Layer], mut state: State) -> Vec<Move> {
+        let mut path = Vec::new();
+        for layer in layers.iter().rev() {
+            match layer[&state].1 {
+                Some((prev, mv)) => {
+                    path.push(mv);
+                    state = prev;
                 }
-                &Instruction {
-                    direction: Direction::Left,
-                    distance,
-                } => {
-                    let from_zero = if x == 0 { 0 } else { 100 - x };
-                    zeros += (distance as i32 + from_zero) / 100;
-                    x -= distance as i32;
+                None => break,
+            }
+        }
+        path.reverse();
+        path
+    }
+
+    /// Turns moves into distances on the actual dial, adding the whole turns
+    /// needed to reach `passes` to the first instructions with room for them.
+    /// Turns that fit nowhere get instructions of their own.
+    pub fn to_instructions(
+        dial: &Dial,
+        moves: &[Move],
+        landings_fixed: bool,
+        passes: Option<u64>,
+    ) -> anyhow::Result<Vec<Instruction>> {
+        let start = dial;
+        let mut dial = dial.clone();
+        let mut instructions = Vec::with_capacity(moves.len());
+        for mv in moves {
+            let to_last_hit = match mv.hits {
+                0 => 0,
+                hits => nth_hit(&dial, mv.direction, hits),
+            };
+            let distance = to_last_hit + !mv.land as u64;
+            dial.rotate(mv.direction, distance);
+            instructions.push(Instruction {
+                direction: mv.direction,
+                distance,
+            });
+        }
+
+        let k = dial.targets.len() as u64;
+        let hits: u64 = moves.iter().map(|mv| mv.hits).sum();
+        let mut turns = passes.map_or(0, |p| (p - hits) / k);
+        // A whole turn ends where it started, so it never changes landings
+        for inst in &mut instructions {
+            let extra = ((u64::MAX - inst.distance) / dial.modulus).min(turns);
+            inst.distance += extra * dial.modulus;
+            turns -= extra;
+        }
+        if turns == 0 {
+            return Ok(instructions);
+        }
+
+        let per_instruction = u64::MAX / dial.modulus;
+        let count = turns.div_ceil(per_instruction);
+        anyhow::ensure!(
+            count <= MAX_EXTRA_INSTRUCTIONS,
+            "Whole turns need {} more instructions, more than {}",
+            count,
+            MAX_EXTRA_INSTRUCTIONS
+        );
+        let at = free_stop(start, &mut instructions, landings_fixed).ok_or_else(|| {
+            anyhow::anyhow!("Instruction distance overflows u64 and every stop lands on a target")
+        })?;
+        let extras = (0..count).map(|i| Instruction {
+            direction: Direction::Right,
+            distance: per_instruction.min(turns - i * per_instruction) * dial.modulus,
+        });
+        instructions.splice(at..at, extras);
+        Ok(instructions)
+    }
+
+    // Index where instructions of whole turns can go without adding landings:
+    // before the first stop off every target. An instruction that clicks past
+    // a free position is split there to make such a stop.
+    fn free_stop(
+        start: &Dial,
+        instructions: &mut Vec<Instruction>,
+        landings_fixed: bool,
+    ) -> Option<usize> {
+        if !landings_fixed {
+            return Some(instructions.len());
+        }
+        let mut dial = start.clone();
+        for i in 0..=instructions.len() {
+            if !dial.on_target() {
+                return Some(i);
+            }
+            let inst = *instructions.get(i)?;
+            // With k targets, one of the next k + 1 positions is free
+            let limit = (inst.distance - 1).min(dial.targets.len() as u64 + 1);
+            let mut probe = dial.clone();
+            for clicks in 1..=limit {
+                probe.rotate(inst.direction, 1);
+                if !probe.on_target() {
+                    instructions[i].distance = clicks;
+                    instructions.insert(
+                        i + 1,
+                        Instruction {
+                            direction: inst.direction,
+                            distance: inst.distance - clicks,
+                        },
+                    );
+                    return Some(i + 1);
                 }
             }
-            x = x.rem_euclid(100);
+            dial.rotate(inst.direction, inst.distance);
         }
-        Ok(format!("Pass by 0 {} times", zeros))
+        None
+    }
+
+    // Clicks until the dial is on a target for the `n`th time
+    fn nth_hit(dial: &Dial, direction: Direction, n: u64) -> u64 {
+        let mut clicks: Vec<u64> = dial
+            .targets
+            .iter()
+            .map(|&t| dial.clicks_to(t, direction))
+            .collect();
+        clicks.sort_unstable();
+        let k = clicks.len() as u64;
+        clicks[((n - 1) % k) as usize] + (n - 1) / k * dial.modulus
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+
+    // Reference solution turning the dial one click at a time.
+    // Returns (times landing on a target, times passing a target).
+    fn naive_count(
+        modulus: u64,
+        start: u64,
+        targets: &[u64],
+        instructions: &[Instruction],
+    ) -> (u64, u128) {
+        let mut x = start;
+        let mut landed = 0;
+        let mut passed = 0;
+        for inst in instructions {
+            for _ in 0..inst.distance {
+                x = match inst.direction {
+                    Direction::Left => (x + modulus - 1) % modulus,
+                    Direction::Right => (x + 1) % modulus,
+                };
+                if targets.contains(&x) {
+                    passed += 1;
+                }
+            }
+            if targets.contains(&x) {
+                landed += 1;
+            }
+        }
+        (landed, passed)
+    }
+
+    fn naive_count_zeros(instructions: &[Instruction]) -> (u64, u128) {
+        naive_count(100, 50, &[0], instructions)
+    }
+
+    fn dials() -> impl Strategy<Value = (u64, u64, Vec<u64>)> {
+        (1u64..150).prop_flat_map(|modulus| {
+            (
+                Just(modulus),
+                0..modulus,
+                prop::collection::vec(0..modulus, 0..4),
+            )
+        })
+    }
+
+    fn format_instructions(instructions: &[Instruction]) -> String {
+        instructions
+            .iter()
+            .map(|inst| format!("{}\n", inst))
+            .collect()
+    }
+
+    fn instructions() -> impl Strategy<Value = Vec<Instruction>> {
+        prop::collection::vec(
+            (any::<bool>(), 0u64..500).prop_map(|(left, distance)| Instruction {
+                direction: if left {
+                    Direction::Left
+                } else {
+                    Direction::Right
+                },
+                distance,
+            }),
+            0..50,
+        )
+    }
+
+    // Reference for `find_instructions`: breadth-first search over the actual
+    // dial positions and counts, trying every distance that could matter
+    fn naive_shortest(
+        modulus: u64,
+        start: u64,
+        targets: &[u64],
+        landings: u64,
+        passes: u64,
+    ) -> Option<usize> {
+        let mut seen = std::collections::HashSet::from([(start, 0, 0)]);
+        let mut layer = vec![(start, 0, 0)];
+        for n in 0.. {
+            if layer.iter().any(|&(_, l, p)| l == landings && p == passes) {
+                return Some(n);
+            }
+            let mut next = Vec::new();
+            for (position, l, p) in layer {
+                for direction in [Direction::Left, Direction::Right] {
+                    for distance in 0..=(passes + 1) * modulus {
+                        let mut dial = Dial::new(modulus, position, targets).unwrap();
+                        let p = p + dial.rotate(direction, distance);
+                        let l = l + dial.on_target() as u64;
+                        let state = (dial.position(), l, p);
+                        if l <= landings && p <= passes && seen.insert(state) {
+                            next.push(state);
+                        }
+                    }
+                }
+            }
+            if next.is_empty() {
+                return None;
+            }
+            layer = next;
+        }
+        unreachable!()
+    }
+
+    proptest! {
+        #[test]
+        fn test_find_instructions_matches_reference(
+            (modulus, start, targets) in (1u64..7).prop_flat_map(|modulus| {
+                (Just(modulus), 0..modulus, prop::collection::vec(0..modulus, 0..4))
+            }),
+            landings in 0u64..4,
+            passes in 0u64..7,
+        ) {
+            let dial = Dial::new(modulus, start, &targets).unwrap();
+            let expected = naive_shortest(modulus, start, &targets, landings, passes);
+            let found = find_instructions(&dial, Some(landings), Some(passes));
+            prop_assert_eq!(found.as_ref().ok().map(|f| f.len()), expected, "{:?}", found);
+        }
+
+        #[test]
+        fn test_part1_matches_reference(instructions in instructions()) {
+            let (landed, _) = naive_count_zeros(&instructions);
+            let output = Day01.part1(&format_instructions(&instructions)).unwrap();
+            prop_assert_eq!(output, format!("Points at 0 {} times", landed));
+        }
+
+        #[test]
+        fn test_part2_matches_reference(instructions in instructions()) {
+            let (_, passed) = naive_count_zeros(&instructions);
+            let output = Day01.part2(&format_instructions(&instructions)).unwrap();
+            prop_assert_eq!(output, format!("Pass by 0 {} times", passed));
+        }
+
+        #[test]
+        fn test_dial_matches_reference(
+            (modulus, start, targets) in dials(),
+            instructions in instructions(),
+        ) {
+            let dial = Dial::new(modulus, start, &targets).unwrap();
+            let (landed, passed) = naive_count(modulus, start, &targets, &instructions);
+            prop_assert_eq!(count_landings(dial.clone(), instructions.clone()), landed);
+            prop_assert_eq!(count_passes(dial, instructions), passed);
+        }
+    }
+
+    const SAMPLE_INPUT: &str = "L68
+L30
+R48
+L5
+R60
+L55
+L1
+L99
+R14
+L82
+";
+
+    #[test]
+    fn test_part1_sample() {
+        let result = Day01.part1(SAMPLE_INPUT).unwrap();
+        assert_eq!(result, "Points at 0 3 times");
+    }
+
+    #[test]
+    fn test_part2_sample() {
+        let result = Day01.part2(SAMPLE_INPUT).unwrap();
+        assert_eq!(result, "Pass by 0 6 times");
+    }
+
+    #[test]
+    fn test_long_left_rotations() {
+        // 50 - 1030 = -980, which wraps to 20 after passing 0 ten times
+        let mut dial = Dial::default();
+        assert_eq!(dial.rotate(Direction::Left, 1030), 10);
+        assert_eq!(dial.position(), 20);
+        assert_eq!(dial.rotate(Direction::Left, 320), 4);
+        assert_eq!(dial.position(), 0);
+
+        // Left of zero must wrap, not stay negative: 50 -> 70 -> 0
+        let result = Day01.part1("L180\nR30\n").unwrap();
+        assert_eq!(result, "Points at 0 1 times");
+        let result = Day01.part2("L180\nR30\n").unwrap();
+        assert_eq!(result, "Pass by 0 3 times");
+        let result = Day01.part1("L250\nL500\nR1\n").unwrap();
+        assert_eq!(result, "Points at 0 2 times");
+        let result = Day01.part2("L250\nL500\nR1\n").unwrap();
+        assert_eq!(result, "Pass by 0 8 times");
+    }
+
+    #[test]
+    fn test_dial_rotate() {
+        let mut dial = Dial::default();
+        assert_eq!(dial.rotate(Direction::Left, 68), 1);
+        assert_eq!(dial.position(), 82);
+        assert_eq!(dial.rotate(Direction::Right, 18), 1);
+        assert!(dial.on_target());
+        assert_eq!(dial.rotate(Direction::Right, 300), 3);
+        assert_eq!(dial.rotate(Direction::Left, 0), 0);
+        assert!(dial.on_target());
+    }
+
+    #[test]
+    fn test_dial_variants() {
+        let instructions = [
+            Instruction {
+                direction: Direction::Right,
+                distance: 30,
+            },
+            Instruction {
+                direction: Direction::Left,
+                distance: 75,
+            },
+        ];
+        // 60 positions starting at 30: 30 -> 0 -> 45
+        let dial = Dial::new(60, 30, &[0]).unwrap();
+        assert_eq!(count_landings(dial.clone(), instructions), 1);
+        assert_eq!(count_passes(dial, instructions), 2);
+
+        // Several targets, the duplicate only counts once
+        let dial = Dial::new(60, 30, &[0, 45, 45, 10]).unwrap();
+        assert_eq!(count_landings(dial.clone(), instructions), 2);
+        assert_eq!(count_passes(dial, instructions), 6);
+    }
+
+    #[test]
+    fn test_trace() {
+        let instructions = parse_input("L68\nL30\nR48\nL5\nR60\nL55\n").unwrap();
+        let steps: Vec<TraceStep> = trace(Dial::default(), instructions).collect();
+        assert_eq!(steps.len(), 6);
+        assert_eq!((steps[0].before, steps[0].after), (50, 82));
+        assert_eq!(steps[0].passes, 1);
+        assert_eq!(steps[2].after, 0);
+        assert_eq!(steps[5].total_landings, 2);
+        assert_eq!(steps[5].total_passes, 4);
+
+        let mut csv = Vec::new();
+        write_trace(&mut csv, steps[..2].iter().copied(), TraceFormat::Csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "step,instruction,before,after,passes,total_landings,total_passes\n\
+             1,L68,50,82,1,0,1\n\
+             2,L30,82,52,0,0,1\n"
+        );
+
+        let mut table = Vec::new();
+        write_trace(&mut table, steps, TraceFormat::Table).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        assert_eq!(table.lines().count(), 7);
+        assert!(
+            table
+                .lines()
+                .all(|line| line.len() == table.lines().next().unwrap().len())
+        );
+    }
+
+    #[test]
+    fn test_find_instructions() {
+        let dial = Dial::default();
+        assert!(
+            find_instructions(&dial, Some(0), Some(0))
+                .unwrap()
+                .is_empty()
+        );
+
+        let instructions = find_instructions(&dial, Some(3), Some(1000)).unwrap();
+        assert_eq!(instructions.len(), 3);
+        let input = instructions
+            .iter()
+            .map(|i| format!("{}\n", i))
+            .collect::<String>();
+        assert_eq!(Day01.part1(&input).unwrap(), "Points at 0 3 times");
+        assert_eq!(Day01.part2(&input).unwrap(), "Pass by 0 1000 times");
+
+        // Passing without landing takes a single long rotation
+        let instructions = find_instructions(&dial, Some(0), Some(7)).unwrap();
+        assert_eq!(instructions.len(), 1);
+        assert_eq!(find_instructions(&dial, None, Some(7)).unwrap().len(), 1);
+        assert_eq!(find_instructions(&dial, Some(4), None).unwrap().len(), 4);
+
+        // Turning by 0 on a target lands on it again without passing it
+        assert!(find_instructions(&dial, Some(1), Some(0)).is_err());
+        assert_eq!(find_instructions(&dial, Some(3), Some(1)).unwrap().len(), 3);
+        let dial = Dial::new(100, 0, &[0]).unwrap();
+        assert_eq!(find_instructions(&dial, Some(2), Some(0)).unwrap().len(), 2);
+
+        // Reaching 0 on this dial always lands on it or carries on to 1
+        let dial = Dial::new(100, 50, &[0, 1]).unwrap();
+        assert!(find_instructions(&dial, Some(0), Some(1)).is_err());
+        assert_eq!(find_instructions(&dial, Some(0), Some(2)).unwrap().len(), 1);
+
+        let dial = Dial::new(10, 5, &[]).unwrap();
+        assert!(find_instructions(&dial, Some(1), None).is_err());
+        assert!(find_instructions(&dial, None, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_instructions_with_long_turns() {
+        let check = |dial: &Dial, landings: u64, passes: u64| {
+            let instructions = find_instructions(dial, Some(landings), Some(passes)).unwrap();
+            assert_eq!(count_landings(dial.clone(), instructions.clone()), landings);
+            assert_eq!(
+                count_passes(dial.clone(), instructions.clone()),
+                passes as u128
+            );
+            instructions
+        };
+        // Too many turns for one instruction
+        assert!(check(&Dial::default(), 5, u64::MAX).len() > 5);
+        // Every turn is a whole instruction, and the dial starts on the target
+        let dial = Dial::new(u64::MAX, 0, &[0]).unwrap();
+        assert_eq!(check(&dial, 3, 10).len(), 11);
+        assert_eq!(check(&dial, 0, 4).len(), 5);
+        assert!(find_instructions(&dial, None, Some(1000)).unwrap().len() >= 1000);
+        // Each extra instruction turns the dial once, so this needs too many
+        let dial = Dial::new(u64::MAX, 1, &[0]).unwrap();
+        assert!(find_instructions(&dial, Some(0), Some(u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_dial_new_rejects_off_dial_positions() {
+        assert!(Dial::new(0, 0, &[]).is_err());
+        assert!(Dial::new(60, 60, &[0]).is_err());
+        assert!(Dial::new(60, 0, &[59, 60]).is_err());
+        assert!(Dial::new(1, 0, &[0]).is_ok());
+    }
 
     #[test]
     fn test_parse_instruction() {
@@ -123,9 +1083,85 @@ mod tests {
     #[test]
     fn test_parse_input() {
         let input = "R27\nL5\nR99\n";
-        let instructions = parse_input(input);
+        let instructions = parse_input(input).unwrap();
         assert_eq!(instructions.len(), 3);
         assert_eq!(instructions[0].distance, 27);
         assert_eq!(instructions[1].direction, Direction::Left);
     }
+
+    #[test]
+    fn test_parse_errors_have_line_numbers() {
+        let err = parse_input("R1\n\nX5\n").unwrap_err();
+        assert!(
+            err.to_string().starts_with("Failed to parse line 3:"),
+            "{}",
+            err
+        );
+        let err = parse_input("R1\nL2 3\n").unwrap_err();
+        assert!(
+            err.to_string().starts_with("Failed to parse line 2:"),
+            "{}",
+            err
+        );
+        assert!(Day01.part2("L1\nR-4\n").is_err());
+        assert_eq!(parse_input("R1\r\n  L2  \n").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_pass_totals_do_not_overflow() {
+        // Each of these passes 0 u64::MAX times on a one-position dial
+        let input = "R18446744073709551615\n".repeat(101);
+        let instructions = parse_input(&input).unwrap();
+        let expected = 101 * u64::MAX as u128;
+        let dial = Dial::new(1, 0, &[0]).unwrap();
+        assert_eq!(count_passes(dial.clone(), instructions.clone()), expected);
+        let last = trace(dial, instructions.clone()).last().unwrap();
+        assert_eq!(last.total_passes, expected);
+        assert_eq!(last.total_landings, 101);
+
+        // On the puzzle's dial the total passes u64::MAX too
+        let passes = count_passes(Dial::default(), instructions);
+        assert!(passes > u64::MAX as u128);
+        assert_eq!(
+            Day01.part2(&input).unwrap(),
+            format!("Pass by 0 {} times", passes)
+        );
+    }
+
+    #[test]
+    fn test_streaming() {
+        // A tiny buffer makes the reader refill in the middle of lines
+        let input = SAMPLE_INPUT.repeat(1000);
+        let reader = io::BufReader::with_capacity(3, input.as_bytes());
+        let passes = with_instructions(reader, |instructions| {
+            count_passes(Dial::default(), instructions)
+        })
+        .unwrap();
+        let expected = count_passes(Dial::default(), parse_input(&input).unwrap());
+        assert_eq!(passes, expected);
+
+        // The closure only sees the instructions before the bad line
+        let mut seen = 0;
+        let result = with_instructions("R1\nL1\nbad\nR1\n".as_bytes(), |instructions| {
+            seen = instructions.count()
+        });
+        assert!(result.is_err());
+        assert_eq!(seen, 2);
+    }
+
+    #[test]
+    fn test_distances_beyond_u32() {
+        let result = Day01.part2("R5000000000\n").unwrap();
+        assert_eq!(result, "Pass by 0 50000000 times");
+        let mut dial = Dial::default();
+        assert_eq!(dial.rotate(Direction::Left, u64::MAX), 184467440737095516);
+        assert_eq!(dial.position(), 35);
+    }
+
+    #[test]
+    fn test_generate() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let instructions = parse_input(&generate(50, &mut rng)).unwrap();
+        assert_eq!(instructions.len(), 50);
+    }
 }
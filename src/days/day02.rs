@@ -1,91 +1,527 @@
-use std::collections::HashSet;
-
 use crate::Solution;
 use nom::{
     IResult, Parser,
-    character::complete::{char, u64 as parse_u64},
+    branch::alt,
+    bytes::complete::take_while1,
+    character::complete::{char, multispace0, multispace1},
+    combinator::{map_res, value},
     multi::separated_list0,
-    sequence::separated_pair,
+    sequence::{delimited, separated_pair},
 };
+use rand::{Rng, rngs::StdRng};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use tracing::instrument;
+
+/// Solver for Day02, with the IDs written in `base`.
+///
+/// Reversed or overlapping ranges are an error, unless `normalize` is set, in
+/// which case they are swapped and merged before solving.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Day02 {
+    pub base: Base,
+    pub normalize: bool,
+}
+
+/// A base between 2 and 36 to write IDs in, checked once on creation so the
+/// functions taking one never divide by zero or run out of digit characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Base(u32);
+
+impl Base {
+    pub const DECIMAL: Base = Base(10);
 
-pub struct Day02;
+    pub fn new(base: u32) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            (2..=36).contains(&base),
+            "Base must be between 2 and 36, not {}",
+            base
+        );
+        Ok(Self(base))
+    }
+
+    pub fn get(self) -> u32 {
+        self.0
+    }
+}
+
+impl Default for Base {
+    fn default() -> Self {
+        Self::DECIMAL
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Range {
-    pub start: u64,
-    pub end: u64,
+    pub start: u128,
+    pub end: u128,
+}
+
+// Digits are case-insensitive, as in `u128::from_str_radix`
+fn parse_number(input: &str, base: u32) -> IResult<&str, u128> {
+    map_res(take_while1(|c: char| c.is_digit(base)), |s| {
+        u128::from_str_radix(s, base)
+    })
+    .parse(input)
 }
 
-fn parse_range(input: &str) -> IResult<&str, Range> {
-    let (input, (start, end)) = separated_pair(parse_u64, char('-'), parse_u64).parse(input)?;
+fn parse_range(input: &str, base: u32) -> IResult<&str, Range> {
+    let number = |input| parse_number(input, base);
+    let (input, (start, end)) = separated_pair(number, char('-'), number).parse(input)?;
     Ok((input, Range { start, end }))
 }
 
-fn parse_input(input: &str) -> IResult<&str, Vec<Range>> {
-    separated_list0(char(','), parse_range).parse(input)
+// Ranges are separated by a comma, whitespace or both, e.g. "1-2, 3-4\n5-6"
+fn parse_separator(input: &str) -> IResult<&str, ()> {
+    alt((
+        value((), (multispace0, char(','), multispace0)),
+        value((), multispace1),
+    ))
+    .parse(input)
 }
 
-impl Solution for Day02 {
-    fn part1(&self, input: &str) -> anyhow::Result<String> {
-        let (_, lines) = parse_input(input).expect("Failed to parse input");
-        let mut set: HashSet<u64> = HashSet::new();
-        for range in &lines {
-            sum_invalid(range, 2, &mut set);
+#[instrument(name = "parse", level = "debug", skip_all)]
+fn parse_input(input: &str, base: u32) -> IResult<&str, Vec<Range>> {
+    delimited(
+        multispace0,
+        separated_list0(parse_separator, |input| parse_range(input, base)),
+        multispace0,
+    )
+    .parse(input)
+}
+
+/// Parses a list of ranges, in input order.
+///
+/// Fails on anything left over, with its line and column, rather than
+/// dropping the ranges after it.
+pub fn parse_ranges(input: &str, base: Base) -> anyhow::Result<Vec<Range>> {
+    let (rest, ranges) = parse_input(input, base.get())
+        .map_err(|e| anyhow::anyhow!("Failed to parse input: {}", e))?;
+    if let Some(found) = rest.lines().next() {
+        let consumed = &input[..input.len() - rest.len()];
+        let line = consumed.matches('\n').count() + 1;
+        let column = consumed.chars().rev().take_while(|&c| c != '\n').count() + 1;
+        anyhow::bail!(
+            "Failed to parse input at line {}, column {}: unexpected {:?}",
+            line,
+            column,
+            found
+        );
+    }
+    Ok(ranges)
+}
+
+/// Checks that no range is reversed and that no two ranges overlap.
+/// Ranges are numbered from 1 in the messages.
+pub fn check_ranges(ranges: &[Range], base: Base) -> anyhow::Result<()> {
+    let show = |i: usize| {
+        let range = &ranges[i];
+        format!(
+            "range {} ({}-{})",
+            i + 1,
+            to_base(range.start, base),
+            to_base(range.end, base)
+        )
+    };
+    if let Some(i) = ranges.iter().position(|r| r.start > r.end) {
+        anyhow::bail!("The {} is reversed", show(i));
+    }
+    let mut order: Vec<usize> = (0..ranges.len()).collect();
+    order.sort_by_key(|&i| ranges[i].start);
+    for pair in order.windows(2) {
+        if ranges[pair[1]].start <= ranges[pair[0]].end {
+            let (a, b) = (pair[0].min(pair[1]), pair[0].max(pair[1]));
+            anyhow::bail!("The {} overlaps the {}", show(a), show(b));
         }
-        let total_sum_invalid: u64 = set.iter().sum();
+    }
+    Ok(())
+}
+
+/// Swaps the ends of reversed ranges, then sorts them and merges
+/// overlapping ones.
+pub fn normalize_ranges(ranges: &[Range]) -> Vec<Range> {
+    let swapped: Vec<Range> = ranges
+        .iter()
+        .map(|r| Range {
+            start: r.start.min(r.end),
+            end: r.start.max(r.end),
+        })
+        .collect();
+    merge_ranges(&swapped)
+}
+
+/// Entry point for the `parse_day02` fuzz target.
+#[cfg(fuzzing)]
+pub fn fuzz_parse(input: &str) {
+    for base in [2, 10, 36] {
+        let base = Base(base);
+        if let Ok(ranges) = parse_ranges(input, base) {
+            let _ = check_ranges(&ranges, base);
+            let _ = normalize_ranges(&ranges);
+        }
+    }
+}
+
+/// Generates `size` disjoint ranges on a single line, in random order.
+/// Each range spans at most one change in digit count.
+pub fn generate(size: usize, rng: &mut StdRng) -> String {
+    let mut ranges: Vec<Range> = Vec::with_capacity(size);
+    while ranges.len() < size {
+        let length = rng.random_range(1..=12);
+        let start = rng.random_range(10u128.pow(length - 1)..10u128.pow(length));
+        let end = start + rng.random_range(0..=start / 2 + 10);
+        let range = Range { start, end };
+        let overlaps = ranges
+            .iter()
+            .any(|r| r.start <= range.end && range.start <= r.end);
+        if !overlaps {
+            ranges.push(range);
+        }
+    }
+    let ranges: Vec<String> = ranges
+        .iter()
+        .map(|r| format!("{}-{}", r.start, r.end))
+        .collect();
+    format!("{}\n", ranges.join(","))
+}
+
+/// How many times a block of digits has to repeat for an ID to be invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeats {
+    Exactly(u32),
+    AtLeast(u32),
+}
+
+impl Day02 {
+    fn solve(&self, input: &str, repeats: Repeats) -> anyhow::Result<String> {
+        let lines = parse_ranges(input, self.base)?;
+        let count = lines.len();
+        let ranges = if self.normalize {
+            normalize_ranges(&lines)
+        } else {
+            check_ranges(&lines, self.base)?;
+            lines
+        };
+        let total_sum_invalid = ranges.iter().try_fold(0u128, |total, range| {
+            let sum = sum_invalid(range, repeats, self.base)?;
+            total.checked_add(sum).ok_or_else(overflow)
+        })?;
+        let total = if self.base == Base::DECIMAL {
+            total_sum_invalid.to_string()
+        } else {
+            format!(
+                "{} (decimal {})",
+                to_base(total_sum_invalid, self.base),
+                total_sum_invalid
+            )
+        };
         Ok(format!(
-            "Parsed {} ranges, sum of invalid: {:?}",
-            lines.len(),
-            total_sum_invalid
+            "Parsed {} ranges, sum of invalid: {}",
+            count, total
         ))
     }
+}
+
+impl Solution for Day02 {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        self.solve(input, Repeats::Exactly(2))
+    }
 
     fn part2(&self, input: &str) -> anyhow::Result<String> {
-        let lines = parse_input(input).expect("Failed to parse input").1;
-        let mut set: HashSet<u64> = HashSet::new();
-        for range in &lines {
-            for k in 2..=num_length(range.end) {
-                sum_invalid(range, k, &mut set);
+        self.solve(input, Repeats::AtLeast(2))
+    }
+}
+
+/// Formats `n` in `base`, with lowercase letters for digits above 9.
+pub fn to_base(mut n: u128, base: Base) -> String {
+    let base = base.get();
+    let mut digits = Vec::new();
+    loop {
+        digits.push(std::char::from_digit((n % base as u128) as u32, base).unwrap());
+        n /= base as u128;
+        if n == 0 {
+            break;
+        }
+    }
+    digits.iter().rev().collect()
+}
+
+/// Sorts the ranges and merges overlapping ones, so that no ID is counted twice.
+fn merge_ranges(ranges: &[Range]) -> Vec<Range> {
+    let mut sorted = ranges.to_vec();
+    sorted.sort_by_key(|r| r.start);
+    let mut merged: Vec<Range> = Vec::with_capacity(sorted.len());
+    for range in sorted {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Splits a range into pieces whose numbers all have the same digit count.
+fn split_by_length(range: &Range, base: u32) -> Vec<Range> {
+    let mut pieces = Vec::new();
+    let mut start = range.start;
+    while start <= range.end {
+        let end = (base as u128)
+            .checked_pow(num_length(start, base))
+            .map_or(range.end, |next| range.end.min(next - 1));
+        pieces.push(Range { start, end });
+        if end == range.end {
+            break;
+        }
+        start = end + 1;
+    }
+    pieces
+}
+
+fn overflow() -> anyhow::Error {
+    anyhow::anyhow!("Sum of invalid IDs does not fit in a u128")
+}
+
+/// Whether the decimal digits of `n` are a block repeated at least
+/// `min_repeats` times.
+pub fn is_invalid(n: u128, min_repeats: u32) -> bool {
+    is_repeated(n, Repeats::AtLeast(min_repeats), Base::DECIMAL)
+}
+
+/// Whether the digits of `n` in `base` are a block repeated as `repeats` asks.
+pub fn is_repeated(n: u128, repeats: Repeats, base: Base) -> bool {
+    let base = base.get();
+    let length = num_length(n, base);
+    let single = Range { start: n, end: n };
+    periods(length, repeats)
+        .into_iter()
+        .any(|period| blocks(&single, length, period, base).is_some())
+}
+
+/// Sum of the invalid IDs in `range`, computed without visiting them.
+/// Digit blocks are taken in `base`.
+///
+/// Fails if the sum does not fit in a `u128`.
+pub fn sum_invalid(range: &Range, repeats: Repeats, base: Base) -> anyhow::Result<u128> {
+    let base = base.get();
+    split_by_length(range, base)
+        .iter()
+        .try_fold(0u128, |total, piece| {
+            let sum = inclusion_exclusion(piece, repeats, base, Blocks::sum)?;
+            total.checked_add(sum).ok_or_else(overflow)
+        })
+}
+
+/// Number of invalid IDs in `range`, computed without visiting them.
+pub fn count_invalid(range: &Range, repeats: Repeats, base: Base) -> u128 {
+    let base = base.get();
+    split_by_length(range, base)
+        .iter()
+        .map(|piece| {
+            inclusion_exclusion(piece, repeats, base, |blocks| Ok(blocks.count()))
+                .expect("there are fewer invalid IDs than u128 values")
+        })
+        .sum()
+}
+
+/// The invalid IDs of `range` in ascending order, see [`invalid_ids`].
+pub struct InvalidIds {
+    base: u32,
+    repeats: Repeats,
+    pieces: std::vec::IntoIter<Range>,
+    // One arithmetic progression per block length of the current piece:
+    // next value, step and last value
+    progressions: BinaryHeap<Reverse<(u128, u128, u128)>>,
+    previous: Option<u128>,
+}
+
+impl Iterator for InvalidIds {
+    type Item = u128;
+
+    fn next(&mut self) -> Option<u128> {
+        loop {
+            let Some(Reverse((value, step, last))) = self.progressions.pop() else {
+                let piece = self.pieces.next()?;
+                let length = num_length(piece.start, self.base);
+                for period in periods(length, self.repeats) {
+                    if let Some(blocks) = blocks(&piece, length, period, self.base) {
+                        let first = blocks.first * blocks.repeat;
+                        let last = blocks.last * blocks.repeat;
+                        self.progressions
+                            .push(Reverse((first, blocks.repeat, last)));
+                    }
+                }
+                continue;
+            };
+            if value < last {
+                self.progressions.push(Reverse((value + step, step, last)));
+            }
+            // An ID repeating a short block also repeats longer ones, e.g.
+            // 1111 is both 1 and 11 repeated
+            if self.previous != Some(value) {
+                self.previous = Some(value);
+                return Some(value);
             }
         }
-        let total_sum_invalid: u64 = set.iter().sum();
-        Ok(format!(
-            "Parsed {} ranges, sum of invalid: {:?}",
-            lines.len(),
-            total_sum_invalid
-        ))
     }
 }
-fn sum_invalid(range: &Range, k: u32, set: &mut HashSet<u64>) {
-    // dbg!((range, k));
-    let start_len = num_length(range.start);
-    let end_len = num_length(range.end);
 
-    // If either is even, divide that length by 2
-    let n = if start_len % k == 0 || end_len % k == 0 {
-        end_len / k
+/// Iterates over the invalid IDs of `range` in ascending order, lazily, so
+/// it is fine to take a few from a huge range.
+pub fn invalid_ids(range: &Range, repeats: Repeats, base: Base) -> InvalidIds {
+    let base = base.get();
+    InvalidIds {
+        base,
+        repeats,
+        pieces: split_by_length(range, base).into_iter(),
+        progressions: BinaryHeap::new(),
+        previous: None,
+    }
+}
+
+// Block lengths that make an ID of `length` digits invalid
+fn periods(length: u32, repeats: Repeats) -> Vec<u32> {
+    match repeats {
+        Repeats::Exactly(k) if k > 0 && length > 0 && length.is_multiple_of(k) => {
+            vec![length / k]
+        }
+        Repeats::Exactly(_) => Vec::new(),
+        Repeats::AtLeast(m) => (1..=length)
+            .filter(|&d| length.is_multiple_of(d) && length / d >= m)
+            .collect(),
+    }
+}
+
+// An ID of `length` digits is a block of `period` digits repeated iff
+// `period` divides `length`; it may also repeat a shorter block.
+//
+// Let f(d) be the sum (or count) of the IDs that repeat a block of length d,
+// and g(p) that of the IDs whose shortest block has length p. Then f(d) is
+// the sum of g(p) over the p dividing d, and Möbius inversion gives g(p) as
+// the sum of mu(p / d) f(d) over the d dividing p. Summing g over the allowed
+// p counts every ID once, without a set.
+fn inclusion_exclusion(
+    range: &Range,
+    repeats: Repeats,
+    base: u32,
+    f: impl Fn(&Blocks) -> anyhow::Result<u128>,
+) -> anyhow::Result<u128> {
+    let length = num_length(range.start, base);
+    let allowed = periods(length, repeats);
+    if let Repeats::Exactly(_) = repeats {
+        // At most one block length, so nothing is counted twice
+        return match allowed.first() {
+            Some(&period) => blocks(range, length, period, base).map_or(Ok(0), |b| f(&b)),
+            None => Ok(0),
+        };
+    }
+
+    let divisors: Vec<u32> = (1..=length).filter(|&d| length.is_multiple_of(d)).collect();
+    // The terms can add up past u128 before the subtractions bring the total
+    // back, so count the carries
+    let mut carries = 0i64;
+    let mut total = 0u128;
+    for &d in &divisors {
+        // Coefficient of f(d): mu(p / d) over the allowed shortest blocks p
+        // that d divides
+        let coefficient: i32 = allowed
+            .iter()
+            .filter(|&&p| p.is_multiple_of(d))
+            .map(|&p| mobius(p / d))
+            .sum();
+        if coefficient == 0 {
+            continue;
+        }
+        // A nonzero coefficient means d is an allowed block length itself,
+        // so f(d) is at most the answer
+        let term = match blocks(range, length, d, base) {
+            Some(blocks) => f(&blocks)?,
+            None => continue,
+        };
+        for _ in 0..coefficient.unsigned_abs() {
+            let (new_total, carried) = if coefficient > 0 {
+                total.overflowing_add(term)
+            } else {
+                total.overflowing_sub(term)
+            };
+            total = new_total;
+            carries += carried as i64 * coefficient.signum() as i64;
+        }
+    }
+    if carries == 0 {
+        Ok(total)
     } else {
-        return;
-    };
-    // Find the number of
-    let div = (0..k).into_iter().map(|i| 10u64.pow(i * n)).sum::<u64>();
-    let mut a = (range.start + div - 1) / div;
-    let mut b = range.end / div;
-    if end_len % k != 0 {
-        b = 10u64.pow(n) - 1;
+        Err(overflow())
     }
-    if start_len % k != 0 {
-        a = 10u64.pow(n - 1);
+}
+
+// The IDs of the range that repeat a block of `period` digits: the blocks
+// `first..=last` times `repeat` = 1 + base^period + base^(2 period) ...
+struct Blocks {
+    repeat: u128,
+    first: u128,
+    last: u128,
+}
+
+impl Blocks {
+    fn count(&self) -> u128 {
+        self.last - self.first + 1
     }
-    for i in a..=b {
-        set.insert(i * div);
+
+    // Arithmetic series first + (first + 1) + ... + last, times `repeat`
+    fn sum(&self) -> anyhow::Result<u128> {
+        let count = self.count();
+        let ends = self.first.checked_add(self.last).ok_or_else(overflow)?;
+        // Halve whichever factor is even
+        let blocks = if count.is_multiple_of(2) {
+            (count / 2).checked_mul(ends)
+        } else {
+            (ends / 2).checked_mul(count)
+        };
+        blocks
+            .and_then(|blocks| blocks.checked_mul(self.repeat))
+            .ok_or_else(overflow)
     }
 }
 
-fn num_length(mut n: u64) -> u32 {
+// The powers below base^length fit since the range has numbers that long, and
+// so does `repeat`, which is at most the largest of them.
+fn blocks(range: &Range, length: u32, period: u32, base: u32) -> Option<Blocks> {
+    let base = base as u128;
+    let repeat: u128 = (0..length / period).map(|i| base.pow(i * period)).sum();
+    let lowest_block = base.pow(period - 1);
+    let highest_block = base.checked_pow(period).map_or(u128::MAX, |p| p - 1);
+    let first = range.start.div_ceil(repeat).max(lowest_block);
+    let last = (range.end / repeat).min(highest_block);
+    (first <= last).then_some(Blocks {
+        repeat,
+        first,
+        last,
+    })
+}
+
+fn mobius(mut n: u32) -> i32 {
+    let mut result = 1;
+    let mut p = 2;
+    while p * p <= n {
+        if n.is_multiple_of(p) {
+            n /= p;
+            if n.is_multiple_of(p) {
+                return 0;
+            }
+            result = -result;
+        }
+        p += 1;
+    }
+    if n > 1 {
+        result = -result;
+    }
+    result
+}
+
+fn num_length(mut n: u128, base: u32) -> u32 {
     let mut length = 0;
     while n > 0 {
-        n /= 10;
+        n /= base as u128;
         length += 1;
     }
     length
@@ -94,10 +530,87 @@ fn num_length(mut n: u64) -> u32 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+
+    // Reference check on the decimal string: is it some block repeated
+    // exactly `repeats` times?
+    fn naive_is_repeated(n: u128, repeats: usize) -> bool {
+        let s = n.to_string();
+        s.len().is_multiple_of(repeats) && s == s[..s.len() / repeats].repeat(repeats)
+    }
+
+    // Reference solution checking every number of every range.
+    // `part2` allows any number of repeats instead of exactly two.
+    fn naive_sum_invalid(ranges: &[Range], part2: bool) -> u128 {
+        let mut set = std::collections::HashSet::new();
+        for range in ranges {
+            for n in range.start..=range.end {
+                let invalid = if part2 {
+                    (2..=n.to_string().len()).any(|k| naive_is_repeated(n, k))
+                } else {
+                    naive_is_repeated(n, 2)
+                };
+                if invalid {
+                    set.insert(n);
+                }
+            }
+        }
+        set.iter().sum()
+    }
+
+    // Mostly narrow ranges like the puzzle input, plus short ones that cross
+    // several digit lengths
+    fn ranges() -> impl Strategy<Value = Vec<Range>> {
+        let range = prop_oneof![(1u128..2_000_000, 0u128..5_000), (1u128..100, 0u128..5_000),]
+            .prop_map(|(start, width)| Range {
+                start,
+                end: start + width,
+            });
+        prop::collection::vec(range, 1..5)
+    }
+
+    fn format_ranges(ranges: &[Range]) -> String {
+        let ranges: Vec<String> = ranges
+            .iter()
+            .map(|r| format!("{}-{}", r.start, r.end))
+            .collect();
+        ranges.join(",")
+    }
+
+    // The generated ranges may overlap
+    const NORMALIZE: Day02 = Day02 {
+        base: Base::DECIMAL,
+        normalize: true,
+    };
+
+    proptest! {
+        #[test]
+        fn test_part1_matches_reference(ranges in ranges()) {
+            let output = NORMALIZE.part1(&format_ranges(&ranges)).unwrap();
+            let expected = format!(
+                "Parsed {} ranges, sum of invalid: {}",
+                ranges.len(),
+                naive_sum_invalid(&ranges, false)
+            );
+            prop_assert_eq!(output, expected);
+        }
+
+        #[test]
+        fn test_part2_matches_reference(ranges in ranges()) {
+            let output = NORMALIZE.part2(&format_ranges(&ranges)).unwrap();
+            let expected = format!(
+                "Parsed {} ranges, sum of invalid: {}",
+                ranges.len(),
+                naive_sum_invalid(&ranges, true)
+            );
+            prop_assert_eq!(output, expected);
+        }
+    }
 
     #[test]
     fn test_parse_range() {
-        let (_, range) = parse_range("851786270-851907437").unwrap();
+        let (_, range) = parse_range("851786270-851907437", 10).unwrap();
         assert_eq!(range.start, 851786270);
         assert_eq!(range.end, 851907437);
     }
@@ -105,7 +618,7 @@ mod tests {
     #[test]
     fn test_parse_line() {
         let input = "851786270-851907437,27-47,577-1044";
-        let (_, ranges) = parse_input(input).unwrap();
+        let (_, ranges) = parse_input(input, 10).unwrap();
         assert_eq!(ranges.len(), 3);
         assert_eq!(ranges[0].start, 851786270);
         assert_eq!(ranges[0].end, 851907437);
@@ -116,9 +629,400 @@ mod tests {
     #[test]
     fn test_parse_input() {
         let input = "851786270-851907437,27-47,2-17\n";
-        let lines = parse_input(input).unwrap().1;
+        let lines = parse_input(input, 10).unwrap().1;
         assert_eq!(lines.len(), 3);
         assert_eq!(lines[2].start, 2);
         assert_eq!(lines[2].end, 17);
     }
+
+    #[test]
+    fn test_split_by_length() {
+        let pieces = split_by_length(
+            &Range {
+                start: 5,
+                end: 1234,
+            },
+            10,
+        );
+        assert_eq!(
+            pieces,
+            vec![
+                Range { start: 5, end: 9 },
+                Range { start: 10, end: 99 },
+                Range {
+                    start: 100,
+                    end: 999
+                },
+                Range {
+                    start: 1000,
+                    end: 1234
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_ranges() {
+        let ranges = [
+            Range { start: 20, end: 30 },
+            Range { start: 1, end: 5 },
+            Range { start: 25, end: 40 },
+            Range { start: 5, end: 5 },
+        ];
+        assert_eq!(
+            merge_ranges(&ranges),
+            vec![Range { start: 1, end: 5 }, Range { start: 20, end: 40 }]
+        );
+        let output = NORMALIZE.part2("11-22,11-22,20-99").unwrap();
+        assert_eq!(output, "Parsed 3 ranges, sum of invalid: 495");
+    }
+
+    #[test]
+    fn test_parse_separators() {
+        let input = " 11-22, 95-115\n998-1012 ,\n1188511880-1188511890\r\n\t222220-222224\n";
+        let ranges = parse_ranges(input, Base::DECIMAL).unwrap();
+        assert_eq!(ranges.len(), 5);
+        assert_eq!(
+            ranges[2],
+            Range {
+                start: 998,
+                end: 1012
+            }
+        );
+        assert_eq!(ranges[4].end, 222224);
+        assert_eq!(parse_ranges("", Base::DECIMAL).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_parse_trailing_input() {
+        let err = parse_ranges("11-22,33-44\n55-66;77-88\n", Base::DECIMAL).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to parse input at line 2, column 6: unexpected \";77-88\""
+        );
+        let err = parse_ranges("11-22,", Base::DECIMAL).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to parse input at line 1, column 6: unexpected \",\""
+        );
+        // Too big for a u128
+        let input = format!("1-{}0", u128::MAX);
+        assert!(parse_ranges(&input, Base::DECIMAL).is_err());
+        assert!(Day02::default().part1("11-22,x").is_err());
+    }
+
+    #[test]
+    fn test_check_ranges() {
+        let ranges = parse_ranges("11-22,50-10", Base::DECIMAL).unwrap();
+        let err = check_ranges(&ranges, Base::DECIMAL).unwrap_err();
+        assert_eq!(err.to_string(), "The range 2 (50-10) is reversed");
+        let ranges = parse_ranges("40-50,1-5,11-22,20-30", Base(16)).unwrap();
+        let err = check_ranges(&ranges, Base(16)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The range 3 (11-22) overlaps the range 4 (20-30)"
+        );
+        let ranges = parse_ranges("1-10,5-5", Base::DECIMAL).unwrap();
+        assert!(check_ranges(&ranges, Base::DECIMAL).is_err());
+        let ranges = parse_ranges("40-50,1-5,11-22,23-30", Base::DECIMAL).unwrap();
+        assert!(check_ranges(&ranges, Base::DECIMAL).is_ok());
+    }
+
+    #[test]
+    fn test_normalize() {
+        let ranges = parse_ranges("95-115 20-11 11-11 100-200", Base::DECIMAL).unwrap();
+        assert_eq!(
+            normalize_ranges(&ranges),
+            vec![
+                Range { start: 11, end: 20 },
+                Range {
+                    start: 95,
+                    end: 200
+                },
+            ]
+        );
+        assert!(Day02::default().part1("22-11").is_err());
+        let output = NORMALIZE.part1("22-11").unwrap();
+        assert_eq!(output, "Parsed 1 ranges, sum of invalid: 33");
+    }
+
+    #[test]
+    fn test_sum_invalid_repeats() {
+        let range = Range {
+            start: 1,
+            end: 1_000_000,
+        };
+        let naive = |invalid: &dyn Fn(u128) -> bool| -> u128 {
+            (range.start..=range.end).filter(|&n| invalid(n)).sum()
+        };
+        let length = |n: u128| n.to_string().len();
+        assert_eq!(
+            sum_invalid(&range, Repeats::Exactly(3), Base::DECIMAL).unwrap(),
+            naive(&|n| naive_is_repeated(n, 3))
+        );
+        assert_eq!(
+            sum_invalid(&range, Repeats::AtLeast(3), Base::DECIMAL).unwrap(),
+            naive(&|n| (3..=length(n)).any(|k| naive_is_repeated(n, k)))
+        );
+        assert_eq!(
+            sum_invalid(&range, Repeats::AtLeast(1), Base::DECIMAL).unwrap(),
+            500_000_500_000
+        );
+        assert_eq!(
+            sum_invalid(&range, Repeats::Exactly(7), Base::DECIMAL).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_sum_invalid_wide_ranges() {
+        // Splitting a range anywhere must not change the total
+        let whole = Range {
+            start: 1,
+            end: 1_000_000_000_000_000_000,
+        };
+        let left = Range {
+            start: 1,
+            end: 123_456_789_012_345,
+        };
+        let right = Range {
+            start: 123_456_789_012_346,
+            end: whole.end,
+        };
+        for repeats in [Repeats::Exactly(2), Repeats::AtLeast(2)] {
+            let total = sum_invalid(&whole, repeats, Base::DECIMAL).unwrap();
+            assert!(total > 0);
+            assert_eq!(
+                sum_invalid(&left, repeats, Base::DECIMAL).unwrap()
+                    + sum_invalid(&right, repeats, Base::DECIMAL).unwrap(),
+                total
+            );
+        }
+        let full = Range {
+            start: 1,
+            end: u64::MAX as u128,
+        };
+        assert!(
+            sum_invalid(&full, Repeats::AtLeast(2), Base::DECIMAL).unwrap()
+                > sum_invalid(&whole, Repeats::AtLeast(2), Base::DECIMAL).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_u128_ranges() {
+        // Past u64::MAX = 18446744073709551615
+        let input = "18446744081844674000-18446744081844675000";
+        let (_, ranges) = parse_input(input, 10).unwrap();
+        let expected = naive_sum_invalid(&ranges, true);
+        assert!(expected > u64::MAX as u128);
+        let output = Day02::default().part2(input).unwrap();
+        assert_eq!(
+            output,
+            format!("Parsed 1 ranges, sum of invalid: {}", expected)
+        );
+
+        // The largest repdigit below u128::MAX is 33...3, with 39 digits
+        let ones: u128 = "1".repeat(39).parse().unwrap();
+        let top = Range {
+            start: 3 * ones - 5,
+            end: u128::MAX,
+        };
+        assert_eq!(
+            sum_invalid(&top, Repeats::AtLeast(39), Base::DECIMAL).unwrap(),
+            3 * ones
+        );
+        assert_eq!(
+            sum_invalid(&top, Repeats::Exactly(39), Base::DECIMAL).unwrap(),
+            3 * ones
+        );
+        assert_eq!(
+            sum_invalid(&top, Repeats::Exactly(2), Base::DECIMAL).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_overflow_is_an_error() {
+        let wide = Range {
+            start: 1,
+            end: u128::MAX,
+        };
+        for repeats in [Repeats::Exactly(2), Repeats::AtLeast(2)] {
+            let err = sum_invalid(&wide, repeats, Base::DECIMAL).unwrap_err();
+            assert_eq!(err.to_string(), "Sum of invalid IDs does not fit in a u128");
+        }
+        let input = format!("1-{}", u128::MAX);
+        assert!(Day02::default().part1(&input).is_err());
+        // Each range fits on its own, the total does not
+        let twos = "2".repeat(39);
+        let threes = "3".repeat(39);
+        let input = format!("{0}-{0}", threes);
+        assert!(Day02::default().part2(&input).is_ok());
+        let input = format!("{0}-{0},{1}-{1}", twos, threes);
+        assert!(Day02::default().part2(&input).is_err());
+    }
+
+    #[test]
+    fn test_zero_is_valid() {
+        let output = Day02::default().part2("0-11").unwrap();
+        assert_eq!(output, "Parsed 1 ranges, sum of invalid: 11");
+    }
+
+    #[test]
+    fn test_bases() {
+        let range = Range {
+            start: 1,
+            end: 5000,
+        };
+        for base in [2, 3, 7, 16, 36].map(Base) {
+            let repeated = |n: u128, k: usize| {
+                let s = to_base(n, base);
+                s.len().is_multiple_of(k) && s == s[..s.len() / k].repeat(k)
+            };
+            let naive = |invalid: &dyn Fn(u128) -> bool| -> u128 {
+                (range.start..=range.end).filter(|&n| invalid(n)).sum()
+            };
+            assert_eq!(
+                sum_invalid(&range, Repeats::Exactly(2), base).unwrap(),
+                naive(&|n| repeated(n, 2)),
+                "base {}",
+                base.get()
+            );
+            assert_eq!(
+                sum_invalid(&range, Repeats::AtLeast(2), base).unwrap(),
+                naive(&|n| (2..=64).any(|k| repeated(n, k))),
+                "base {}",
+                base.get()
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_and_print_in_base() {
+        let (_, ranges) = parse_input("a-FF,1z-zz", 36).unwrap();
+        assert_eq!(
+            ranges[0],
+            Range {
+                start: 10,
+                end: 555
+            }
+        );
+        assert_eq!(
+            ranges[1],
+            Range {
+                start: 71,
+                end: 1295
+            }
+        );
+
+        // 0x11 + 0x22 + ... + 0xff = 17 * (1 + ... + 15)
+        let output = Day02 {
+            base: Base(16),
+            ..Default::default()
+        }
+        .part1("a-ff")
+        .unwrap();
+        assert_eq!(
+            output,
+            "Parsed 1 ranges, sum of invalid: 7f8 (decimal 2040)"
+        );
+        // In binary, 5 = 101 is not a repeated block but 3 = 11 is
+        let output = Day02 {
+            base: Base(2),
+            ..Default::default()
+        }
+        .part2("10-110")
+        .unwrap();
+        assert_eq!(output, "Parsed 1 ranges, sum of invalid: 11 (decimal 3)");
+
+        for base in [0, 1, 37] {
+            let err = Base::new(base).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("Base must be between 2 and 36, not {}", base)
+            );
+        }
+        assert_eq!(to_base(0, Base(2)), "0");
+        let max = to_base(u128::MAX, Base(36));
+        assert_eq!(u128::from_str_radix(&max, 36), Ok(u128::MAX));
+    }
+
+    #[test]
+    fn test_invalid_ids() {
+        let range = Range {
+            start: 1,
+            end: 20_000,
+        };
+        for base in [2, 10, 16].map(Base) {
+            for repeats in [
+                Repeats::Exactly(2),
+                Repeats::Exactly(3),
+                Repeats::AtLeast(2),
+            ] {
+                let naive: Vec<u128> = (range.start..=range.end)
+                    .filter(|&n| {
+                        let s = to_base(n, base);
+                        let repeated =
+                            |k: usize| s.len().is_multiple_of(k) && s == s[..s.len() / k].repeat(k);
+                        match repeats {
+                            Repeats::Exactly(k) => repeated(k as usize),
+                            Repeats::AtLeast(m) => (m as usize..=s.len()).any(repeated),
+                        }
+                    })
+                    .collect();
+                let ids: Vec<u128> = invalid_ids(&range, repeats, base).collect();
+                assert_eq!(ids, naive, "{:?} in base {}", repeats, base.get());
+                assert_eq!(count_invalid(&range, repeats, base), naive.len() as u128);
+                assert_eq!(
+                    sum_invalid(&range, repeats, base).unwrap(),
+                    naive.iter().sum::<u128>()
+                );
+                assert!(ids.iter().all(|&n| is_repeated(n, repeats, base)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_ids_are_lazy() {
+        let full = Range {
+            start: 1,
+            end: u128::MAX,
+        };
+        let first: Vec<u128> = invalid_ids(&full, Repeats::AtLeast(2), Base::DECIMAL)
+            .take(5)
+            .collect();
+        assert_eq!(first, vec![11, 22, 33, 44, 55]);
+        // 1111 repeats both 1 and 11 but comes out once
+        let range = Range {
+            start: 1100,
+            end: 1300,
+        };
+        let ids: Vec<u128> = invalid_ids(&range, Repeats::AtLeast(2), Base::DECIMAL).collect();
+        assert_eq!(ids, vec![1111, 1212]);
+        // Only 1...1, 2...2 and 3...3 have 39 digits and fit in a u128
+        assert_eq!(count_invalid(&full, Repeats::Exactly(39), Base::DECIMAL), 3);
+    }
+
+    #[test]
+    fn test_is_invalid() {
+        assert!(is_invalid(1212, 2));
+        assert!(is_invalid(121212, 3));
+        assert!(is_invalid(777, 3));
+        assert!(!is_invalid(1212, 3));
+        assert!(!is_invalid(1231, 2));
+        assert!(!is_invalid(0, 2));
+    }
+
+    #[test]
+    fn test_mobius() {
+        let values: Vec<i32> = (1..=12).map(mobius).collect();
+        assert_eq!(values, vec![1, -1, -1, 0, -1, 1, -1, 0, 0, 1, -1, 0]);
+    }
+
+    #[test]
+    fn test_generate() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let (_, ranges) = parse_input(&generate(50, &mut rng), 10).unwrap();
+        assert_eq!(ranges.len(), 50);
+    }
 }
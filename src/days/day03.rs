@@ -1,89 +1,601 @@
 use crate::Solution;
-use nom::{
-    IResult, Parser,
-    character::complete::{digit1, newline},
-    combinator::map,
-    multi::separated_list1,
-};
+use rand::{Rng, rngs::StdRng};
+use tracing::{instrument, warn};
 
-pub struct Day03;
+/// Solver for Day03. `digits` overrides how many batteries are turned on
+/// per bank, which is otherwise 2 in part 1 and 12 in part 2.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Day03 {
+    pub digits: Option<usize>,
+    pub short_banks: ShortBanks,
+}
+
+/// What to do with a bank that has fewer batteries than have to be turned on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShortBanks {
+    #[default]
+    Error,
+    /// Leave the bank out of the total, with a warning
+    Skip,
+}
 
 type Grid = Vec<Vec<u8>>;
 
-fn parse_digit(c: char) -> u8 {
-    c.to_digit(10).unwrap() as u8
+fn parse_digit(c: char) -> Option<u8> {
+    c.to_digit(10).map(|d| d as u8)
 }
 
-fn parse_line(input: &str) -> IResult<&str, Vec<u8>> {
-    map(digit1, |s: &str| s.chars().map(parse_digit).collect()).parse(input)
+fn parse_line(line: &str) -> anyhow::Result<Vec<u8>> {
+    anyhow::ensure!(!line.is_empty(), "no batteries");
+    line.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            parse_digit(c)
+                .ok_or_else(|| anyhow::anyhow!("{:?} at column {} is not a digit", c, i + 1))
+        })
+        .collect()
 }
 
-fn parse_input(input: &str) -> IResult<&str, Grid> {
-    separated_list1(newline, parse_line).parse(input)
+// Banks are lines of digits. Blank lines at the end are ignored, but not
+// between banks, where they are most likely a mistake.
+#[instrument(name = "parse", level = "debug", skip_all)]
+fn parse_input(input: &str) -> anyhow::Result<Grid> {
+    let grid: Grid = input
+        .trim_end()
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            parse_line(line.trim_end_matches('\r'))
+                .map_err(|e| anyhow::anyhow!("Failed to parse line {}: {}", i + 1, e))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    anyhow::ensure!(!grid.is_empty(), "Failed to parse input: no banks");
+    for row in ragged_rows(&grid) {
+        warn!(
+            "Line {} has {} batteries, but line 1 has {}",
+            row + 1,
+            grid[row].len(),
+            grid[0].len()
+        );
+    }
+    Ok(grid)
 }
 
-impl Solution for Day03 {
-    fn part1(&self, input: &str) -> anyhow::Result<String> {
-        let (_, grid) = parse_input(input).expect("Failed to parse input");
-        let mut joltage = 0u64;
-        for line in &grid {
-            joltage += find_joltage::<2>(line);
+/// Rows, counted from 0, whose length differs from that of the first row.
+pub fn ragged_rows(grid: &[Vec<u8>]) -> Vec<usize> {
+    let Some(first) = grid.first() else {
+        return Vec::new();
+    };
+    (1..grid.len())
+        .filter(|&row| grid[row].len() != first.len())
+        .collect()
+}
+
+/// Entry point for the `parse_day03` fuzz target.
+#[cfg(fuzzing)]
+pub fn fuzz_parse(input: &str) {
+    let _ = parse_input(input);
+}
+
+/// Generates `size` banks of 100 random non-zero digits.
+pub fn generate(size: usize, rng: &mut StdRng) -> String {
+    let mut out = String::new();
+    for _ in 0..size {
+        for _ in 0..100 {
+            out.push(char::from(b'0' + rng.random_range(1..=9)));
         }
+        out.push('\n');
+    }
+    out
+}
+
+// Batteries turned on per bank in each part
+const PART_DIGITS: [usize; 2] = [2, 12];
+
+impl Day03 {
+    fn solve(&self, input: &str, digits: usize) -> anyhow::Result<String> {
+        let grid = parse_input(input)?;
+        let k = self.digits.unwrap_or(digits);
         Ok(format!(
             "Parsed grid: {} rows x {} cols, total joltage {}",
             grid.len(),
             grid[0].len(),
-            joltage
+            total_joltage(&grid, k, self.short_banks)?
         ))
     }
+}
+
+impl Solution for Day03 {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        self.solve(input, PART_DIGITS[0])
+    }
 
     fn part2(&self, input: &str) -> anyhow::Result<String> {
-        let (_, grid) = parse_input(input).expect("Failed to parse input");
-        let mut total_joltage = 0u64;
-        let m = grid.len();
-        let n = grid[0].len();
-        for line in &grid {
-            total_joltage += find_joltage::<12>(line);
+        self.solve(input, PART_DIGITS[1])
+    }
+}
+
+impl Day03 {
+    /// Prints every bank with the batteries chosen for `part` highlighted,
+    /// followed by its joltage. Without `color` the chosen batteries are
+    /// marked with `^` on the line below instead.
+    pub fn render(&self, input: &str, part: u8, color: bool) -> anyhow::Result<String> {
+        let grid = parse_input(input)?;
+        let k = self.digits.unwrap_or(PART_DIGITS[part as usize - 1]);
+        let mut out = String::new();
+        for (row, line) in grid.iter().enumerate() {
+            match choose_batteries(line, k) {
+                Some(choice) => out.push_str(&render_choice(line, &choice, color)),
+                None => {
+                    short_bank(row, line.len(), k, self.short_banks)?;
+                    let digits: String = line.iter().map(|&d| char::from(b'0' + d)).collect();
+                    out.push_str(&format!("{} skipped\n", digits));
+                }
+            }
         }
-        Ok(format!(
-            "Parsed grid: {} rows x {} cols, total joltage {}",
-            m, n, total_joltage
-        ))
+        Ok(out)
     }
 }
 
-fn find_joltage<const N: usize>(line: &[u8]) -> u64 {
-    let n = line.len();
-    let mut noi: [u8; N] = line[n - N..].try_into().unwrap();
-    for &digit in line[..n - N].iter().rev() {
-        let mut newnoi = noi;
-        if digit >= noi[0] {
-            newnoi[0] = digit;
+/// The batteries turned on in a bank: their positions, in increasing order,
+/// and the joltage they give.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Choice {
+    pub indices: Vec<usize>,
+    pub joltage: Vec<u8>,
+}
+
+// Fails on a bank too short to turn on `k` batteries, or warns that it is
+// skipped
+fn short_bank(row: usize, len: usize, k: usize, short_banks: ShortBanks) -> anyhow::Result<()> {
+    let message = format!(
+        "Bank on line {} has {} batteries, fewer than {}",
+        row + 1,
+        len,
+        k
+    );
+    match short_banks {
+        ShortBanks::Error => Err(anyhow::anyhow!(message)),
+        ShortBanks::Skip => {
+            warn!("{}, skipping it", message);
+            Ok(())
+        }
+    }
+}
+
+// One bank with its chosen digits in bold green, or marked below them
+fn render_choice(line: &[u8], choice: &Choice, color: bool) -> String {
+    let joltage: String = choice
+        .joltage
+        .iter()
+        .map(|&d| char::from(b'0' + d))
+        .collect();
+    let mut chosen = choice.indices.iter().peekable();
+    let mut digits = String::new();
+    let mut marks = String::new();
+    for (i, &digit) in line.iter().enumerate() {
+        let picked = chosen.next_if_eq(&&i).is_some();
+        let digit = char::from(b'0' + digit);
+        if picked && color {
+            digits.push_str(&format!("\x1b[1;32m{}\x1b[0m", digit));
         } else {
-            continue;
+            digits.push(digit);
         }
-        for i in 1..N {
-            if noi[i - 1] >= noi[i] {
-                newnoi[i] = noi[i - 1];
-            } else {
-                break;
-            }
+        marks.push(if picked { '^' } else { ' ' });
+    }
+    if color {
+        format!("{} {}\n", digits, joltage)
+    } else {
+        format!("{} {}\n{}\n", digits, joltage, marks.trim_end())
+    }
+}
+
+/// Picks `k` digits of `line`, in order, forming the largest number, or
+/// `None` if the line has fewer than `k` digits.
+///
+/// A digit is dropped whenever a larger one follows it and enough digits are
+/// left to still pick `k`, so the kept digits form a stack that is
+/// non-increasing except at its tail, and each digit is pushed and popped at
+/// most once. Among equal digits the earliest are kept.
+pub fn choose_batteries(line: &[u8], k: usize) -> Option<Choice> {
+    if line.len() < k {
+        return None;
+    }
+    let mut stack: Vec<usize> = Vec::with_capacity(k);
+    for (i, &digit) in line.iter().enumerate() {
+        let left = line.len() - i;
+        while stack.last().is_some_and(|&top| line[top] < digit) && stack.len() + left > k {
+            stack.pop();
+        }
+        if stack.len() < k {
+            stack.push(i);
+        }
+    }
+    let joltage = stack.iter().map(|&i| line[i]).collect();
+    Some(Choice {
+        indices: stack,
+        joltage,
+    })
+}
+
+/// The digits of the largest number made of `k` digits of `line`, see
+/// [`choose_batteries`].
+pub fn find_joltage(line: &[u8], k: usize) -> Option<Vec<u8>> {
+    choose_batteries(line, k).map(|choice| choice.joltage)
+}
+
+/// Sum of the joltages of all banks, in decimal. It has no size limit, as
+/// more than 19 digits no longer fit in a `u64`.
+///
+/// Banks with fewer than `k` batteries are handled as `short_banks` says.
+pub fn total_joltage(
+    grid: &[Vec<u8>],
+    k: usize,
+    short_banks: ShortBanks,
+) -> anyhow::Result<String> {
+    // Least significant digit first
+    let mut total: Vec<u8> = Vec::new();
+    for (row, line) in grid.iter().enumerate() {
+        match find_joltage(line, k) {
+            Some(joltage) => add_decimal(&mut total, &joltage),
+            None => short_bank(row, line.len(), k, short_banks)?,
+        }
+    }
+    if total.is_empty() {
+        return Ok("0".to_string());
+    }
+    Ok(total.iter().rev().map(|&d| char::from(b'0' + d)).collect())
+}
+
+// Adds the digits of a number, most significant first, to a total stored
+// least significant digit first
+fn add_decimal(total: &mut Vec<u8>, digits: &[u8]) {
+    let mut digits = digits.iter().rev().copied();
+    let mut carry = 0;
+    let mut i = 0;
+    loop {
+        let digit = digits.next();
+        if digit.is_none() && carry == 0 {
+            break;
+        }
+        if i == total.len() {
+            total.push(0);
         }
-        noi = newnoi;
+        let sum = total[i] + digit.unwrap_or(0) + carry;
+        total[i] = sum % 10;
+        carry = sum / 10;
+        i += 1;
+    }
+    while total.len() > 1 && total.last() == Some(&0) {
+        total.pop();
     }
-    let joltage = {
-        let mut acc = 0u64;
-        for &digit in &noi[..] {
-            acc = acc * 10 + digit as u64;
+}
+
+// next[i][d] is the first position at or after i holding digit d, or the
+// length of the line if there is none
+fn next_positions(line: &[u8]) -> Vec<[usize; 10]> {
+    let mut next = vec![[line.len(); 10]; line.len() + 1];
+    for i in (0..line.len()).rev() {
+        next[i] = next[i + 1];
+        next[i][line[i] as usize] = i;
+    }
+    next
+}
+
+/// The distinct joltages of `k` batteries of a bank, largest first, see
+/// [`distinct_joltages`].
+pub struct DistinctJoltages {
+    k: usize,
+    next: Vec<[usize; 10]>,
+    // One frame per digit being chosen: the position its search starts at
+    // and the digit chosen there so far, 10 before the first try
+    frames: Vec<(usize, u8)>,
+    prefix: Vec<u8>,
+}
+
+impl Iterator for DistinctJoltages {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let n = self.next.len() - 1;
+        loop {
+            let (start, tried) = self.frames.last_mut()?;
+            let left = self.k - self.prefix.len();
+            // The largest untried digit that still leaves room for the rest
+            let found = (0..*tried).rev().find_map(|d| {
+                let i = self.next[*start][d as usize];
+                (n - i >= left).then_some((d, i))
+            });
+            let Some((digit, i)) = found else {
+                self.frames.pop();
+                self.prefix.pop();
+                continue;
+            };
+            *tried = digit;
+            self.prefix.push(digit);
+            if self.prefix.len() == self.k {
+                let joltage = self.prefix.clone();
+                self.prefix.pop();
+                return Some(joltage);
+            }
+            self.frames.push((i + 1, 10));
         }
-        acc
+    }
+}
+
+/// Iterates over the distinct numbers made of `k` digits of `line`, in
+/// order, from the largest down. Taking the best `m` costs O(10 m k) after an
+/// O(10 n) table, without looking at all the choices of `k` digits.
+///
+/// Each number is read off its leftmost occurrence in the line, so a depth
+/// first search trying larger digits first visits every number once, in
+/// descending order, and never reaches a dead end.
+pub fn distinct_joltages(line: &[u8], k: usize) -> DistinctJoltages {
+    let frames = if k > 0 && k <= line.len() {
+        vec![(0, 10)]
+    } else {
+        Vec::new()
     };
-    joltage
+    DistinctJoltages {
+        k,
+        next: next_positions(line),
+        frames,
+        prefix: Vec::with_capacity(k),
+    }
+}
+
+/// Number of ways to pick batteries of `line` giving `joltage`, saturating
+/// at `u128::MAX`.
+pub fn count_choices(line: &[u8], joltage: &[u8]) -> u128 {
+    // ways[t]: ways to pick the first t digits of the joltage so far
+    let mut ways = vec![0u128; joltage.len() + 1];
+    ways[0] = 1;
+    for &digit in line {
+        for t in (1..=joltage.len()).rev() {
+            if joltage[t - 1] == digit {
+                ways[t] = ways[t].saturating_add(ways[t - 1]);
+            }
+        }
+    }
+    ways[joltage.len()]
+}
+
+/// Number of distinct joltages of `k` batteries of `line`, saturating at
+/// `u128::MAX`.
+pub fn count_distinct_joltages(line: &[u8], k: usize) -> u128 {
+    let n = line.len();
+    if k == 0 || k > n {
+        return 0;
+    }
+    let next = next_positions(line);
+    // counts[i]: distinct numbers of `left` digits taken from line[i..],
+    // counted from their leftmost occurrence, for increasing `left`
+    let mut counts = vec![1u128; n + 1];
+    for _ in 0..k {
+        let mut longer = vec![0u128; n + 1];
+        for i in 0..=n {
+            longer[i] = (0..10)
+                .map(|d| next[i][d])
+                .filter(|&j| j < n)
+                .fold(0u128, |sum, j| sum.saturating_add(counts[j + 1]));
+        }
+        counts = longer;
+    }
+    counts[0]
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+
+    // Reference solution trying every choice of `k` digits
+    fn naive_joltage(line: &[u8], k: usize) -> Vec<u8> {
+        if k == 0 || line.len() < k {
+            return Vec::new();
+        }
+        let mut with_first = vec![line[0]];
+        with_first.extend(naive_joltage(&line[1..], k - 1));
+        if line.len() > k {
+            with_first.max(naive_joltage(&line[1..], k))
+        } else {
+            with_first
+        }
+    }
+
+    fn to_u64(digits: &[u8]) -> u64 {
+        digits.iter().fold(0, |acc, &d| acc * 10 + d as u64)
+    }
+
+    proptest! {
+        #[test]
+        fn test_find_joltage_matches_reference(
+            line in prop::collection::vec(0u8..10, 1..14),
+            k in 1usize..14,
+        ) {
+            let expected = (k <= line.len()).then(|| naive_joltage(&line, k));
+            prop_assert_eq!(find_joltage(&line, k), expected);
+        }
+
+        #[test]
+        fn test_total_joltage_matches_u64_sum(
+            grid in prop::collection::vec(prop::collection::vec(0u8..10, 12..17), 1..20)
+        ) {
+            let expected: u64 = grid.iter().map(|line| to_u64(&find_joltage(line, 12).unwrap())).sum();
+            prop_assert_eq!(total_joltage(&grid, 12, ShortBanks::Error).unwrap(), expected.to_string());
+        }
+    }
+
+    #[test]
+    fn test_long_joltages() {
+        let line: Vec<u8> = (0..30).map(|i| (i * 7 % 10) as u8).collect();
+        let joltage = find_joltage(&line, 25).unwrap();
+        assert_eq!(joltage.len(), 25);
+        assert_eq!(joltage, naive_joltage(&line, 25));
+        // Two banks of 30 nines add up to 1 followed by 29 nines and an 8
+        let nines = vec![vec![9; 30], vec![9; 30]];
+        assert_eq!(
+            total_joltage(&nines, 30, ShortBanks::Error).unwrap(),
+            format!("1{}8", "9".repeat(29))
+        );
+        assert_eq!(
+            total_joltage(&[vec![0, 0, 0]], 2, ShortBanks::Error).unwrap(),
+            "0"
+        );
+    }
+
+    #[test]
+    fn test_short_banks() {
+        assert_eq!(find_joltage(&[1, 2], 3), None);
+        let grid = [vec![1, 2, 3], vec![4, 5], vec![6, 7, 8, 9]];
+        let err = total_joltage(&grid, 3, ShortBanks::Error).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Bank on line 2 has 2 batteries, fewer than 3"
+        );
+        assert_eq!(total_joltage(&grid, 3, ShortBanks::Skip).unwrap(), "912");
+        let day = Day03 {
+            digits: Some(20),
+            ..Default::default()
+        };
+        assert!(day.part1("987654321111111").is_err());
+        assert!(Day03::default().part1("1").is_err());
+        let skip = Day03 {
+            short_banks: ShortBanks::Skip,
+            ..Default::default()
+        };
+        assert!(skip.part1("1\n23\n").unwrap().ends_with("total joltage 23"));
+        assert_eq!(
+            skip.render("1\n23\n", 1, false).unwrap(),
+            "1 skipped\n23 23\n^^\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse_input("123\n4x6\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to parse line 2: 'x' at column 2 is not a digit"
+        );
+        let err = parse_input("123\n\n456\n").unwrap_err();
+        assert_eq!(err.to_string(), "Failed to parse line 2: no batteries");
+        assert!(parse_input("").is_err());
+        assert!(parse_input("\n\n").is_err());
+        assert!(Day03::default().part2("12 34").is_err());
+        assert_eq!(
+            parse_input("12\r\n34\n\n").unwrap(),
+            vec![vec![1, 2], vec![3, 4]]
+        );
+    }
+
+    #[test]
+    fn test_ragged_rows() {
+        let grid = parse_input("123\n45\n678\n9\n").unwrap();
+        assert_eq!(ragged_rows(&grid), vec![1, 3]);
+        assert!(ragged_rows(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_choose_batteries() {
+        let line = [8, 1, 8, 1, 8, 1, 9, 1, 1, 1, 1, 2, 1, 1, 1];
+        let choice = choose_batteries(&line, 12).unwrap();
+        assert_eq!(
+            choice.indices,
+            vec![0, 2, 4, 6, 7, 8, 9, 10, 11, 12, 13, 14]
+        );
+        assert_eq!(choice.joltage, vec![8, 8, 8, 9, 1, 1, 1, 1, 2, 1, 1, 1]);
+        // Ties keep the earliest battery
+        assert_eq!(choose_batteries(&[5, 5, 5], 2).unwrap().indices, vec![0, 1]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_choice_indices_give_joltage(
+            line in prop::collection::vec(0u8..10, 1..40),
+            k in 1usize..40,
+        ) {
+            prop_assume!(k <= line.len());
+            let choice = choose_batteries(&line, k).unwrap();
+            prop_assert!(choice.indices.windows(2).all(|w| w[0] < w[1]));
+            let digits: Vec<u8> = choice.indices.iter().map(|&i| line[i]).collect();
+            prop_assert_eq!(digits, choice.joltage);
+        }
+    }
+
+    // Every distinct value of `k` digits of `line` with its number of choices,
+    // largest first
+    fn naive_distinct(line: &[u8], k: usize) -> Vec<(Vec<u8>, u128)> {
+        let mut counts = std::collections::BTreeMap::new();
+        for mask in 0u32..1 << line.len() {
+            if mask.count_ones() as usize == k {
+                let digits: Vec<u8> = (0..line.len())
+                    .filter(|&i| mask & (1 << i) != 0)
+                    .map(|i| line[i])
+                    .collect();
+                *counts.entry(digits).or_insert(0) += 1;
+            }
+        }
+        counts.into_iter().rev().collect()
+    }
+
+    proptest! {
+        #[test]
+        fn test_distinct_joltages_match_reference(
+            line in prop::collection::vec(0u8..4, 0..12),
+            k in 1usize..6,
+        ) {
+            let expected = naive_distinct(&line, k);
+            let values: Vec<Vec<u8>> = distinct_joltages(&line, k).collect();
+            let expected_values: Vec<Vec<u8>> = expected.iter().map(|(v, _)| v.clone()).collect();
+            prop_assert_eq!(&values, &expected_values);
+            prop_assert_eq!(count_distinct_joltages(&line, k), expected.len() as u128);
+            for (value, ways) in &expected {
+                prop_assert_eq!(count_choices(&line, value), *ways);
+            }
+        }
+    }
+
+    #[test]
+    fn test_top_joltages() {
+        let line = [8, 1, 8, 1, 8, 1, 9, 1, 1, 1, 1, 2, 1, 1, 1];
+        let top: Vec<Vec<u8>> = distinct_joltages(&line, 2).take(4).collect();
+        assert_eq!(top, vec![vec![9, 2], vec![9, 1], vec![8, 9], vec![8, 8]]);
+        assert_eq!(count_choices(&line, &[8, 8]), 3);
+        assert_eq!(count_choices(&line, &[9, 1]), 7);
+        // Far too many choices to list them all
+        let line: Vec<u8> = (0..1000).map(|i| (i * 37 % 10) as u8).collect();
+        let top: Vec<Vec<u8>> = distinct_joltages(&line, 500).take(3).collect();
+        assert_eq!(top[0], find_joltage(&line, 500).unwrap());
+        assert!(top[0] > top[1] && top[1] > top[2]);
+        assert_eq!(count_distinct_joltages(&line, 500), u128::MAX);
+    }
+
+    #[test]
+    fn test_render() {
+        let input = "987654321111111\n811111111111119\n";
+        let plain = Day03::default().render(input, 1, false).unwrap();
+        assert_eq!(
+            plain,
+            "987654321111111 98\n^^\n811111111111119 89\n^             ^\n"
+        );
+        let color = Day03::default().render(input, 1, true).unwrap();
+        assert!(color.starts_with("\x1b[1;32m9\x1b[0m\x1b[1;32m8\x1b[0m7654"));
+        assert!(Day03::default().render("1", 2, false).is_err());
+    }
+
+    #[test]
+    fn test_runtime_digits() {
+        let input = "987654321111111\n811111111111119";
+        let day = Day03 {
+            digits: Some(3),
+            ..Default::default()
+        };
+        let output = day.part1(input).unwrap();
+        assert!(output.ends_with("total joltage 1806"), "{}", output);
+        assert_eq!(day.part2(input).unwrap(), output);
+    }
 
     #[test]
     fn test_part1_sample() {
@@ -91,7 +603,7 @@ mod tests {
 811111111111119
 234234234234278
 818181911112111";
-        let output = Day03.part1(TEST_INPUT).unwrap();
+        let output = Day03::default().part1(TEST_INPUT).unwrap();
         assert!(output.contains("357"), "Unexpected output: {}", output);
     }
     #[test]
@@ -100,7 +612,7 @@ mod tests {
 811111111111119
 234234234234278
 818181911112111";
-        let output = Day03.part2(TEST_INPUT).unwrap();
+        let output = Day03::default().part2(TEST_INPUT).unwrap();
         assert!(
             output.contains("3121910778619"),
             "Unexpected output: {}",
@@ -110,7 +622,7 @@ mod tests {
     #[test]
     fn test_parse_line() {
         let input = "987654321111111";
-        let (_, digits) = parse_line(input).unwrap();
+        let digits = parse_line(input).unwrap();
         assert_eq!(digits.len(), 15);
         assert_eq!(digits[0], 9);
         assert_eq!(digits[1], 8);
@@ -124,7 +636,7 @@ mod tests {
 811111111111119
 234234234234278
 818181911112111";
-        let (_, grid) = parse_input(input).unwrap();
+        let grid = parse_input(input).unwrap();
         assert_eq!(grid.len(), 4);
         assert_eq!(grid[0].len(), 15);
         assert_eq!(grid[0][0], 9);
@@ -135,8 +647,17 @@ mod tests {
 
     #[test]
     fn test_parse_digit() {
-        assert_eq!(parse_digit('0'), 0);
-        assert_eq!(parse_digit('5'), 5);
-        assert_eq!(parse_digit('9'), 9);
+        assert_eq!(parse_digit('0'), Some(0));
+        assert_eq!(parse_digit('5'), Some(5));
+        assert_eq!(parse_digit('9'), Some(9));
+        assert_eq!(parse_digit('a'), None);
+    }
+
+    #[test]
+    fn test_generate() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let grid = parse_input(&generate(20, &mut rng)).unwrap();
+        assert_eq!(grid.len(), 20);
+        assert!(grid.iter().all(|line| line.len() == 100));
     }
 }
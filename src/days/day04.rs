@@ -7,8 +7,177 @@ use nom::{
     multi::{many1, separated_list1},
     sequence::terminated,
 };
+use rand::{Rng, rngs::StdRng};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{debug, instrument, trace};
 
-pub struct Day04;
+/// Solver for Day04, removing the rolls that `rule` says a forklift can reach.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Day04 {
+    pub rule: Rule,
+}
+
+/// When a forklift can reach a roll: if the number of rolls among its
+/// neighbours compares to `threshold` as `comparison` says, with neighbours
+/// past the edges of the grid handled as `edges` says. The puzzle's rule is
+/// fewer than 4 of the 8 surrounding cells, with nothing outside the grid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub neighborhood: Neighborhood,
+    pub threshold: usize,
+    pub comparison: Comparison,
+    pub edges: Edges,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self {
+            neighborhood: Neighborhood::Moore,
+            threshold: 4,
+            comparison: Comparison::Less,
+            edges: Edges::Empty,
+        }
+    }
+}
+
+/// What lies past the edges of the grid.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Edges {
+    /// Nothing: neighbours outside the grid are never rolls
+    #[default]
+    Empty,
+    /// Rolls that can never be removed
+    Rolls,
+    /// The grid wraps around, left to right and top to bottom
+    Torus,
+}
+
+/// Parses "empty", "rolls" or "torus".
+impl FromStr for Edges {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s.trim() {
+            "empty" => Edges::Empty,
+            "rolls" => Edges::Rolls,
+            "torus" => Edges::Torus,
+            _ => anyhow::bail!("Unknown edges {:?}, expected empty, rolls or torus", s),
+        })
+    }
+}
+
+impl Rule {
+    pub fn is_reachable(&self, neighbor_rolls: usize) -> bool {
+        self.comparison.holds(neighbor_rolls, self.threshold)
+    }
+}
+
+/// The cells around a roll that count as its neighbours.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Neighborhood {
+    /// The 4 orthogonally adjacent cells
+    VonNeumann,
+    /// The 8 cells around, diagonals included
+    Moore,
+    /// (row, column) offsets, none of them (0, 0) and none repeated
+    Custom(Vec<(isize, isize)>),
+}
+
+impl Neighborhood {
+    pub fn offsets(&self) -> Vec<(isize, isize)> {
+        match self {
+            Neighborhood::VonNeumann => vec![(-1, 0), (0, -1), (0, 1), (1, 0)],
+            Neighborhood::Moore => vec![
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ],
+            Neighborhood::Custom(offsets) => offsets.clone(),
+        }
+    }
+}
+
+/// Parses "moore", "von-neumann" or custom offsets as "row,col" pairs
+/// separated by spaces or semicolons, e.g. "-1,0;1,0".
+impl FromStr for Neighborhood {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim() {
+            "moore" => return Ok(Neighborhood::Moore),
+            "von-neumann" | "vonneumann" => return Ok(Neighborhood::VonNeumann),
+            _ => {}
+        }
+        let mut offsets = Vec::new();
+        for pair in s.split([';', ' ']).filter(|pair| !pair.is_empty()) {
+            let offset = pair
+                .split_once(',')
+                .and_then(|(row, col)| Some((row.parse().ok()?, col.parse().ok()?)))
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Invalid offset {:?}, expected \"row,col\"", pair)
+                })?;
+            anyhow::ensure!(offset != (0, 0), "A roll cannot neighbour itself");
+            anyhow::ensure!(
+                !offsets.contains(&offset),
+                "Offset {:?} is listed twice",
+                pair
+            );
+            offsets.push(offset);
+        }
+        anyhow::ensure!(!offsets.is_empty(), "No neighbourhood offsets given");
+        Ok(Neighborhood::Custom(offsets))
+    }
+}
+
+/// How the number of neighbouring rolls compares to the threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    pub fn holds(self, count: usize, threshold: usize) -> bool {
+        match self {
+            Comparison::Less => count < threshold,
+            Comparison::LessOrEqual => count <= threshold,
+            Comparison::Equal => count == threshold,
+            Comparison::NotEqual => count != threshold,
+            Comparison::GreaterOrEqual => count >= threshold,
+            Comparison::Greater => count > threshold,
+        }
+    }
+}
+
+/// Parses an operator such as "<" or its name such as "lt".
+impl FromStr for Comparison {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s.trim() {
+            "<" | "lt" => Comparison::Less,
+            "<=" | "le" => Comparison::LessOrEqual,
+            "=" | "==" | "eq" => Comparison::Equal,
+            "!=" | "ne" => Comparison::NotEqual,
+            ">=" | "ge" => Comparison::GreaterOrEqual,
+            ">" | "gt" => Comparison::Greater,
+            _ => anyhow::bail!("Unknown comparison {:?}, expected one of < <= = != >= >", s),
+        })
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Cell {
@@ -40,20 +209,56 @@ fn parse_input(input: &str) -> IResult<&str, Grid> {
     separated_list1(newline, parse_line).parse(input)
 }
 
+#[instrument(name = "parse", level = "debug", skip_all)]
 fn parse_input_complete(input: &str) -> IResult<&str, Grid> {
     terminated(parse_input, many1(newline)).parse(input)
 }
 
+// Parses the grid, with or without a final newline, and checks that every
+// row is as long as the first
+fn read_grid(input: &str) -> anyhow::Result<Grid> {
+    let (_, grid) = parse_input_complete(input)
+        .or_else(|_| parse_input(input))
+        .map_err(|e| anyhow::anyhow!("Failed to parse input: {}", e))?;
+    let cols = grid[0].len();
+    if let Some((row, line)) = grid.iter().enumerate().find(|(_, l)| l.len() != cols) {
+        anyhow::bail!(
+            "Failed to parse line {}: expected {} cells, found {}",
+            row + 1,
+            cols,
+            line.len()
+        );
+    }
+    Ok(grid)
+}
+
+/// Entry point for the `parse_day04` fuzz target.
+#[cfg(fuzzing)]
+pub fn fuzz_parse(input: &str) {
+    let _ = parse_input_complete(input);
+    let _ = parse_input(input);
+}
+
+/// Generates a `size` x `size` grid where roughly 60% of cells are paper rolls.
+pub fn generate(size: usize, rng: &mut StdRng) -> String {
+    let mut out = String::new();
+    for _ in 0..size {
+        for _ in 0..size {
+            out.push(if rng.random_bool(0.6) { '@' } else { '.' });
+        }
+        out.push('\n');
+    }
+    out
+}
+
 impl Solution for Day04 {
     fn part1(&self, input: &str) -> anyhow::Result<String> {
-        let (_, grid) = parse_input_complete(input)
-            .or_else(|_| parse_input(input))
-            .expect("Failed to parse input");
+        let grid = read_grid(input)?;
 
         let rows = grid.len();
         let cols = grid.first().map(|r| r.len()).unwrap_or(0);
 
-        let num_reachable_paper_rolls = find_reachable_paper_rolls(&grid).len();
+        let num_reachable_paper_rolls = find_reachable_paper_rolls(&grid, &self.rule).len();
 
         Ok(format!(
             "Parsed grid: {} rows x {} cols. Reachable: {}",
@@ -62,80 +267,370 @@ impl Solution for Day04 {
     }
 
     fn part2(&self, input: &str) -> anyhow::Result<String> {
-        let (_, mut grid) = parse_input_complete(input)
-            .or_else(|_| parse_input(input))
-            .expect("Failed to parse input");
+        let mut grid = read_grid(input)?;
         let mut num_removed = 0;
-        loop {
-            let reachable_rolls = find_reachable_paper_rolls(&grid);
-            if reachable_rolls.is_empty() {
-                break;
-            }
-            for (i, j) in reachable_rolls {
+        for (round, removed) in removal_rounds(&grid, &self.rule).iter().enumerate() {
+            debug!("Round {}: removing {} rolls", round + 1, removed.len());
+            for &(i, j) in removed {
                 grid[i][j] = Cell::Empty;
                 num_removed += 1;
             }
         }
-        print_grid(&grid);
+        trace!("Final grid:\n{}", render_grid(&grid));
         Ok(format!("Removable: {}", num_removed))
     }
 }
 
-fn print_grid(grid: &Grid) {
+impl Day04 {
+    /// Prints the grid with every roll marked by the round it is removed in,
+    /// see [`render_fates`], then the number of rolls removed per round.
+    /// Part 1 stops after the first round.
+    pub fn render(&self, input: &str, part: u8, color: bool) -> anyhow::Result<String> {
+        let grid = read_grid(input)?;
+        let mut rounds = removal_rounds(&grid, &self.rule);
+        if part == 1 {
+            rounds.truncate(1);
+        }
+        let fates = fates(&grid, &rounds);
+        let mut out = render_fates(&fates, color);
+        for (round, removed) in rounds.iter().enumerate() {
+            out.push_str(&format!("Round {}: {} rolls\n", round + 1, removed.len()));
+        }
+        let never = fates
+            .iter()
+            .flatten()
+            .filter(|&&f| f == Fate::Never)
+            .count();
+        out.push_str(&format!("Never: {} rolls\n", never));
+        Ok(out)
+    }
+}
+
+/// What happens to a cell during removal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fate {
+    Empty,
+    /// The roll is removed in this round, counting from 1
+    Removed(usize),
+    /// The roll is never reachable
+    Never,
+}
+
+/// The fate of every cell, given the rolls removed in each round as
+/// returned by [`removal_rounds`].
+pub fn fates(grid: &Grid, rounds: &[Vec<(usize, usize)>]) -> Vec<Vec<Fate>> {
+    let mut fates: Vec<Vec<Fate>> = grid
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| match cell {
+                    Cell::Empty => Fate::Empty,
+                    Cell::PaperRoll => Fate::Never,
+                })
+                .collect()
+        })
+        .collect();
+    for (round, removed) in rounds.iter().enumerate() {
+        for &(i, j) in removed {
+            fates[i][j] = Fate::Removed(round + 1);
+        }
+    }
+    fates
+}
+
+/// Draws removed rolls as their round in base 36 (`+` past round 35), rolls
+/// that stay as `@` and empty cells as `.`. With `color`, rounds also go
+/// from red to blue and the rolls that stay are bold.
+pub fn render_fates(fates: &[Vec<Fate>], color: bool) -> String {
+    // Red, orange, yellow, green, cyan, blue, then blue for every later round
+    const PALETTE: [u8; 6] = [196, 208, 226, 46, 51, 21];
+    let mut out = String::new();
+    for row in fates {
+        for &fate in row {
+            let c = match fate {
+                Fate::Empty => '.',
+                Fate::Never => '@',
+                Fate::Removed(round) => std::char::from_digit(round as u32, 36)
+                    .filter(|_| round < 36)
+                    .unwrap_or('+'),
+            };
+            match (color, fate) {
+                (true, Fate::Removed(round)) => {
+                    let shade = PALETTE[(round - 1).min(PALETTE.len() - 1)];
+                    out.push_str(&format!("\x1b[38;5;{}m{}\x1b[0m", shade, c));
+                }
+                (true, Fate::Never) => out.push_str(&format!("\x1b[1m{}\x1b[0m", c)),
+                _ => out.push(c),
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The grid before a removal round, with the rolls about to be removed. The
+/// last frame shows the final grid with nothing left to remove.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub grid: Grid,
+    pub removing: Vec<(usize, usize)>,
+}
+
+/// One frame per removal round, plus the final grid.
+pub fn frames(grid: &Grid, rule: &Rule) -> Vec<Frame> {
+    let mut grid = grid.clone();
+    let mut frames = Vec::new();
+    for removing in removal_rounds(&grid, rule) {
+        let next = Frame {
+            grid: grid.clone(),
+            removing,
+        };
+        for &(i, j) in &next.removing {
+            grid[i][j] = Cell::Empty;
+        }
+        frames.push(next);
+    }
+    frames.push(Frame {
+        grid,
+        removing: Vec::new(),
+    });
+    frames
+}
+
+/// Plays the frames as a terminal animation, redrawing the screen every
+/// `delay` with the rolls about to go in red.
+pub fn write_animation(out: &mut impl Write, frames: &[Frame], delay: Duration) -> io::Result<()> {
+    for (round, frame) in frames.iter().enumerate() {
+        // Clear the screen and go back to the top left corner
+        write!(out, "\x1b[2J\x1b[H")?;
+        if frame.removing.is_empty() {
+            writeln!(out, "Done after {} rounds", round)?;
+        } else {
+            writeln!(
+                out,
+                "Round {}: removing {} rolls",
+                round + 1,
+                frame.removing.len()
+            )?;
+        }
+        let mut removing = frame.removing.iter().peekable();
+        for (i, row) in frame.grid.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                if removing.next_if_eq(&&(i, j)).is_some() {
+                    write!(out, "\x1b[1;31m@\x1b[0m")?;
+                } else if cell.is_paper_roll() {
+                    write!(out, "@")?;
+                } else {
+                    write!(out, "\x1b[2m.\x1b[0m")?;
+                }
+            }
+            writeln!(out)?;
+        }
+        out.flush()?;
+        if round + 1 < frames.len() {
+            std::thread::sleep(delay);
+        }
+    }
+    Ok(())
+}
+
+/// Writes a frame as a binary PPM image, each cell a `scale` x `scale`
+/// square: rolls grey, rolls about to go red, empty cells white.
+pub fn write_ppm(out: &mut impl Write, frame: &Frame, scale: usize) -> io::Result<()> {
+    const EMPTY: [u8; 3] = [255, 255, 255];
+    const ROLL: [u8; 3] = [96, 96, 96];
+    const REMOVING: [u8; 3] = [220, 40, 40];
+    let rows = frame.grid.len();
+    let cols = frame.grid.first().map(|r| r.len()).unwrap_or(0);
+    write!(out, "P6\n{} {}\n255\n", cols * scale, rows * scale)?;
+    let mut removing = vec![vec![false; cols]; rows];
+    for &(i, j) in &frame.removing {
+        removing[i][j] = true;
+    }
+    let mut line = Vec::with_capacity(cols * scale * 3);
+    for (i, row) in frame.grid.iter().enumerate() {
+        line.clear();
+        for (j, cell) in row.iter().enumerate() {
+            let color = if removing[i][j] {
+                REMOVING
+            } else if cell.is_paper_roll() {
+                ROLL
+            } else {
+                EMPTY
+            };
+            for _ in 0..scale {
+                line.extend_from_slice(&color);
+            }
+        }
+        for _ in 0..scale {
+            out.write_all(&line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the frames as `frame_0000.ppm`, `frame_0001.ppm`, ... in `dir`,
+/// creating it if needed.
+pub fn export_ppm_frames(dir: &Path, frames: &[Frame], scale: usize) -> anyhow::Result<()> {
+    anyhow::ensure!(scale > 0, "Scale must be at least 1");
+    fs::create_dir_all(dir)?;
+    for (n, frame) in frames.iter().enumerate() {
+        let path = dir.join(format!("frame_{:04}.ppm", n));
+        let mut out = io::BufWriter::new(fs::File::create(&path)?);
+        write_ppm(&mut out, frame, scale)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+impl Day04 {
+    /// Frames of the removal of the rolls in `input`, see [`frames`].
+    pub fn frames(&self, input: &str) -> anyhow::Result<Vec<Frame>> {
+        let grid = read_grid(input)?;
+        Ok(frames(&grid, &self.rule))
+    }
+}
+
+fn render_grid(grid: &Grid) -> String {
+    let mut out = String::new();
     for row in grid {
         for cell in row {
             let c = match cell {
                 Cell::Empty => '.',
                 Cell::PaperRoll => '@',
             };
-            print!("{}", c);
+            out.push(c);
         }
-        println!();
+        out.push('\n');
     }
+    out
 }
 
-fn neighbors(grid: &Grid, row: usize, col: usize) -> Vec<(usize, usize)> {
-    let mut result = Vec::new();
+// The cells at `offsets` from (row, col), with `None` for those outside the
+// grid. On a torus every offset wraps around to a cell, possibly (row, col)
+// itself on a small grid.
+fn neighbors(
+    grid: &Grid,
+    row: usize,
+    col: usize,
+    offsets: &[(isize, isize)],
+    edges: Edges,
+) -> Vec<Option<(usize, usize)>> {
     let rows = grid.len() as isize;
     let cols = grid.first().map(|r| r.len() as isize).unwrap_or(0);
+    offsets
+        .iter()
+        .map(|(dr, dc)| {
+            let (r, c) = (row as isize + dr, col as isize + dc);
+            match edges {
+                Edges::Torus => Some((r.rem_euclid(rows), c.rem_euclid(cols))),
+                _ => (r >= 0 && r < rows && c >= 0 && c < cols).then_some((r, c)),
+            }
+        })
+        .map(|cell| cell.map(|(r, c)| (r as usize, c as usize)))
+        .collect()
+}
 
-    let directions = [
-        (-1, -1),
-        (-1, 0),
-        (-1, 1),
-        (0, -1),
-        (0, 1),
-        (1, -1),
-        (1, 0),
-        (1, 1),
-    ];
+// Number of neighbouring rolls, given which cells hold a roll
+fn count_neighbor_rolls(
+    grid: &Grid,
+    row: usize,
+    col: usize,
+    offsets: &[(isize, isize)],
+    edges: Edges,
+    is_roll: impl Fn(usize, usize) -> bool,
+) -> usize {
+    neighbors(grid, row, col, offsets, edges)
+        .into_iter()
+        .filter(|&cell| match cell {
+            Some((r, c)) => is_roll(r, c),
+            None => edges == Edges::Rolls,
+        })
+        .count()
+}
 
-    for (dr, dc) in directions.iter() {
-        let new_row = row as isize + dr;
-        let new_col = col as isize + dc;
+/// Removes the reachable rolls all at once, round after round until none is
+/// reachable, and returns the rolls removed in each round in row-major order.
+///
+/// A roll's neighbour count only changes when a roll next to it is removed,
+/// so the counts are kept up to date and each round only checks the rolls
+/// next to those removed in the previous one. That is O(cells + removed rolls
+/// x neighbourhood size) instead of a rescan of the grid every round.
+pub fn removal_rounds(grid: &Grid, rule: &Rule) -> Vec<Vec<(usize, usize)>> {
+    let rows = grid.len();
+    let cols = grid.first().map(|r| r.len()).unwrap_or(0);
+    let offsets = rule.neighborhood.offsets();
+    // Rolls count a removed roll as a neighbour from the opposite offsets
+    let reversed: Vec<(isize, isize)> = offsets.iter().map(|&(dr, dc)| (-dr, -dc)).collect();
 
-        if new_row >= 0 && new_row < rows && new_col >= 0 && new_col < cols {
-            result.push((new_row as usize, new_col as usize));
+    let mut is_roll: Vec<Vec<bool>> = grid
+        .iter()
+        .map(|row| row.iter().map(Cell::is_paper_roll).collect())
+        .collect();
+    let mut counts = vec![vec![0; cols]; rows];
+    let mut candidates = Vec::new();
+    for i in 0..rows {
+        for j in 0..cols {
+            if is_roll[i][j] {
+                counts[i][j] =
+                    count_neighbor_rolls(grid, i, j, &offsets, rule.edges, |r, c| is_roll[r][c]);
+                candidates.push((i, j));
+            }
         }
     }
 
-    result
+    let mut queued = vec![vec![false; cols]; rows];
+    let mut rounds = Vec::new();
+    loop {
+        let mut removed: Vec<(usize, usize)> = candidates
+            .drain(..)
+            .filter(|&(i, j)| {
+                queued[i][j] = false;
+                is_roll[i][j] && rule.is_reachable(counts[i][j])
+            })
+            .collect();
+        if removed.is_empty() {
+            return rounds;
+        }
+        removed.sort_unstable();
+        // Removals within a round are simultaneous, so the counts only
+        // change once all of them have been chosen
+        for &(i, j) in &removed {
+            is_roll[i][j] = false;
+        }
+        for &(i, j) in &removed {
+            for (r, c) in neighbors(grid, i, j, &reversed, rule.edges)
+                .into_iter()
+                .flatten()
+            {
+                if is_roll[r][c] {
+                    counts[r][c] -= 1;
+                    if !queued[r][c] {
+                        queued[r][c] = true;
+                        candidates.push((r, c));
+                    }
+                }
+            }
+        }
+        rounds.push(removed);
+    }
 }
 
-fn find_reachable_paper_rolls(grid: &Grid) -> Vec<(usize, usize)> {
+fn find_reachable_paper_rolls(grid: &Grid, rule: &Rule) -> Vec<(usize, usize)> {
     let rows = grid.len();
     let cols = grid.first().map(|r| r.len()).unwrap_or(0);
+    let offsets = rule.neighborhood.offsets();
 
     let mut reachable_rolls = Vec::new();
 
     for i in 0..rows {
         for j in 0..cols {
             if grid[i][j].is_paper_roll() {
-                let num_neighbor_rolls = neighbors(grid, i, j)
-                    .iter()
-                    .filter(|&&(nr, nc)| grid[nr][nc].is_paper_roll())
-                    .count();
-                if num_neighbor_rolls < 4 {
+                let num_neighbor_rolls =
+                    count_neighbor_rolls(grid, i, j, &offsets, rule.edges, |r, c| {
+                        grid[r][c].is_paper_roll()
+                    });
+                if rule.is_reachable(num_neighbor_rolls) {
                     reachable_rolls.push((i, j));
                 }
             }
@@ -148,6 +643,8 @@ fn find_reachable_paper_rolls(grid: &Grid) -> Vec<(usize, usize)> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use rand::{Rng, SeedableRng};
 
     const SAMPLE_INPUT: &str = "..@@.@@@@.
 @@@.@.@.@@
@@ -163,7 +660,7 @@ mod tests {
 
     #[test]
     fn test_part1_sample() {
-        let output = Day04.part1(SAMPLE_INPUT).unwrap();
+        let output = Day04::default().part1(SAMPLE_INPUT).unwrap();
         assert!(
             output.contains("13"),
             "Expected output to contain '13', got: {}",
@@ -172,10 +669,342 @@ mod tests {
     }
     #[test]
     fn test_part2_sample() {
-        let output = Day04.part2(SAMPLE_INPUT).unwrap();
+        let output = Day04::default().part2(SAMPLE_INPUT).unwrap();
         assert!(output.contains("43"), "Unexpected output: {}", output);
     }
 
+    #[test]
+    fn test_rules() {
+        let (_, grid) = parse_input_complete(SAMPLE_INPUT).unwrap();
+        let count = |rule: Rule| find_reachable_paper_rolls(&grid, &rule).len();
+        // The puzzle's rule spelled out as offsets
+        let moore: Neighborhood = "-1,-1;-1,0;-1,1;0,-1;0,1;1,-1;1,0;1,1".parse().unwrap();
+        assert_eq!(moore.offsets(), Neighborhood::Moore.offsets());
+        assert_eq!(
+            count(Rule {
+                neighborhood: moore,
+                ..Default::default()
+            }),
+            13
+        );
+        // Every roll has fewer than 4 or at least 4 neighbouring rolls
+        let at_least = Rule {
+            comparison: Comparison::GreaterOrEqual,
+            ..Default::default()
+        };
+        assert_eq!(count(at_least), 71 - 13);
+        // Rolls with no roll to their left or right
+        let lonely = Rule {
+            neighborhood: "0,-1 0,1".parse().unwrap(),
+            threshold: 0,
+            comparison: Comparison::Equal,
+            ..Default::default()
+        };
+        let expected: Vec<(usize, usize)> = SAMPLE_INPUT
+            .lines()
+            .enumerate()
+            .flat_map(|(i, line)| {
+                let padded = format!(".{}.", line).into_bytes();
+                (0..line.len())
+                    .filter(move |&j| padded[j..j + 3] == *b".@.")
+                    .map(move |j| (i, j))
+            })
+            .collect();
+        assert_eq!(expected.len(), 13);
+        assert_eq!(find_reachable_paper_rolls(&grid, &lonely), expected);
+        let von_neumann = Rule {
+            neighborhood: Neighborhood::VonNeumann,
+            threshold: 2,
+            comparison: Comparison::LessOrEqual,
+            ..Default::default()
+        };
+        let output = Day04 { rule: von_neumann }.part1(SAMPLE_INPUT).unwrap();
+        assert!(output.ends_with("Reachable: 37"), "{}", output);
+    }
+
+    #[test]
+    fn test_edges() {
+        let grid_of = |input: &str| parse_input_complete(input).unwrap().1;
+        let reachable = |input: &str, edges: Edges| {
+            let rule = Rule {
+                edges,
+                ..Default::default()
+            };
+            find_reachable_paper_rolls(&grid_of(input), &rule).len()
+        };
+        let full = "@@@@\n@@@@\n@@@@\n@@@@\n";
+        // The corners see 3 rolls and the other edge rolls 5
+        assert_eq!(reachable(full, Edges::Empty), 4);
+        // Every roll sees 8 rolls
+        assert_eq!(reachable(full, Edges::Rolls), 0);
+        assert_eq!(reachable(full, Edges::Torus), 0);
+        // On a torus the corners are next to each other
+        let corners = "@..@\n....\n....\n@..@\n";
+        let rule = Rule {
+            threshold: 3,
+            comparison: Comparison::Equal,
+            edges: Edges::Torus,
+            ..Default::default()
+        };
+        assert_eq!(
+            find_reachable_paper_rolls(&grid_of(corners), &rule).len(),
+            4
+        );
+        let rule = Rule {
+            threshold: 5,
+            comparison: Comparison::Equal,
+            edges: Edges::Rolls,
+            ..Default::default()
+        };
+        assert_eq!(
+            find_reachable_paper_rolls(&grid_of(corners), &rule).len(),
+            4
+        );
+        // A single roll on a 1 x 1 torus is its own neighbour 8 times
+        let rule = Rule {
+            threshold: 8,
+            comparison: Comparison::Equal,
+            edges: Edges::Torus,
+            ..Default::default()
+        };
+        assert_eq!(removal_rounds(&grid_of("@\n"), &rule), vec![vec![(0, 0)]]);
+
+        // Nothing can be removed from a full grid surrounded by rolls
+        let day = Day04 {
+            rule: Rule {
+                edges: Edges::Rolls,
+                ..Default::default()
+            },
+        };
+        assert_eq!(day.part2(full).unwrap(), "Removable: 0");
+        assert_eq!(Day04::default().part2(full).unwrap(), "Removable: 4");
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(
+            "moore".parse::<Neighborhood>().unwrap(),
+            Neighborhood::Moore
+        );
+        assert_eq!(
+            "von-neumann".parse::<Neighborhood>().unwrap(),
+            Neighborhood::VonNeumann
+        );
+        assert_eq!(
+            "-2,0 2,0".parse::<Neighborhood>().unwrap(),
+            Neighborhood::Custom(vec![(-2, 0), (2, 0)])
+        );
+        assert!("0,0".parse::<Neighborhood>().is_err());
+        assert!("1,0;1,0".parse::<Neighborhood>().is_err());
+        assert!("1".parse::<Neighborhood>().is_err());
+        assert!("".parse::<Neighborhood>().is_err());
+        assert_eq!("<=".parse::<Comparison>().unwrap(), Comparison::LessOrEqual);
+        assert_eq!("gt".parse::<Comparison>().unwrap(), Comparison::Greater);
+        assert!("=>".parse::<Comparison>().is_err());
+        assert_eq!("torus".parse::<Edges>().unwrap(), Edges::Torus);
+        assert!("wrap".parse::<Edges>().is_err());
+    }
+
+    // Reference removal rescanning the whole grid every round, counting
+    // neighbours on its own
+    fn naive_removal_rounds(grid: &Grid, rule: &Rule) -> Vec<Vec<(usize, usize)>> {
+        let (rows, cols) = (grid.len() as isize, grid[0].len() as isize);
+        let roll_at = |grid: &Grid, r: isize, c: isize| {
+            let inside = (0..rows).contains(&r) && (0..cols).contains(&c);
+            match rule.edges {
+                Edges::Torus => {
+                    grid[r.rem_euclid(rows) as usize][c.rem_euclid(cols) as usize].is_paper_roll()
+                }
+                Edges::Rolls if !inside => true,
+                _ => inside && grid[r as usize][c as usize].is_paper_roll(),
+            }
+        };
+        let mut grid = grid.clone();
+        let mut rounds = Vec::new();
+        loop {
+            let mut removed = Vec::new();
+            for i in 0..rows {
+                for j in 0..cols {
+                    let count = rule
+                        .neighborhood
+                        .offsets()
+                        .iter()
+                        .filter(|&&(dr, dc)| roll_at(&grid, i + dr, j + dc))
+                        .count();
+                    if roll_at(&grid, i, j) && rule.is_reachable(count) {
+                        removed.push((i as usize, j as usize));
+                    }
+                }
+            }
+            if removed.is_empty() {
+                return rounds;
+            }
+            for &(i, j) in &removed {
+                grid[i][j] = Cell::Empty;
+            }
+            rounds.push(removed);
+        }
+    }
+
+    fn grids() -> impl Strategy<Value = Grid> {
+        (1usize..8, 1usize..8).prop_flat_map(|(rows, cols)| {
+            let cell = prop_oneof![Just(Cell::Empty), Just(Cell::PaperRoll)];
+            prop::collection::vec(prop::collection::vec(cell, cols), rows)
+        })
+    }
+
+    fn rules() -> impl Strategy<Value = Rule> {
+        let neighborhood = prop_oneof![
+            Just(Neighborhood::Moore),
+            Just(Neighborhood::VonNeumann),
+            Just("-2,1 0,1 1,-1 1,1".parse().unwrap()),
+        ];
+        let comparison = prop_oneof![
+            Just(Comparison::Less),
+            Just(Comparison::Equal),
+            Just(Comparison::NotEqual),
+            Just(Comparison::GreaterOrEqual),
+        ];
+        let edges = prop_oneof![Just(Edges::Empty), Just(Edges::Rolls), Just(Edges::Torus)];
+        (neighborhood, 0usize..9, comparison, edges).prop_map(
+            |(neighborhood, threshold, comparison, edges)| Rule {
+                neighborhood,
+                threshold,
+                comparison,
+                edges,
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn test_removal_rounds_match_reference(grid in grids(), rule in rules()) {
+            prop_assert_eq!(removal_rounds(&grid, &rule), naive_removal_rounds(&grid, &rule));
+        }
+    }
+
+    #[test]
+    fn test_removal_rounds_match_rescan() {
+        let rules = [
+            Rule::default(),
+            Rule {
+                neighborhood: Neighborhood::VonNeumann,
+                threshold: 2,
+                comparison: Comparison::LessOrEqual,
+                edges: Edges::Rolls,
+            },
+            // Not monotone: removing rolls can make others unreachable again
+            Rule {
+                neighborhood: "-2,1 0,1 1,-1 1,1".parse().unwrap(),
+                threshold: 2,
+                comparison: Comparison::Equal,
+                edges: Edges::Torus,
+            },
+            Rule {
+                threshold: 5,
+                comparison: Comparison::GreaterOrEqual,
+                ..Default::default()
+            },
+            Rule {
+                edges: Edges::Torus,
+                ..Default::default()
+            },
+            Rule {
+                edges: Edges::Rolls,
+                ..Default::default()
+            },
+        ];
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let size = rng.random_range(1..40);
+            let (_, grid) = parse_input_complete(&generate(size, &mut rng)).unwrap();
+            for rule in &rules {
+                assert_eq!(
+                    removal_rounds(&grid, rule),
+                    naive_removal_rounds(&grid, rule),
+                    "seed {} with {:?}",
+                    seed,
+                    rule
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_fates() {
+        let (_, grid) = parse_input_complete(SAMPLE_INPUT).unwrap();
+        let rounds = removal_rounds(&grid, &Rule::default());
+        let counts: Vec<usize> = rounds.iter().map(Vec::len).collect();
+        assert_eq!(counts, vec![13, 12, 7, 5, 2, 1, 1, 1, 1]);
+        let fates = fates(&grid, &rounds);
+        assert_eq!(fates[0][0], Fate::Empty);
+        assert_eq!(fates[0][2], Fate::Removed(1));
+        let never = fates.iter().flatten().filter(|&&f| f == Fate::Never);
+        assert_eq!(never.count(), 71 - 43);
+        let rendered = render_fates(&fates, false);
+        assert_eq!(rendered.lines().next(), Some("..11.1121."));
+        assert_eq!(rendered.matches('@').count(), 28);
+    }
+
+    #[test]
+    fn test_render() {
+        let input = "@@@\n@@@\n";
+        let output = Day04::default().render(input, 2, false).unwrap();
+        assert_eq!(
+            output,
+            "121\n121\nRound 1: 4 rolls\nRound 2: 2 rolls\nNever: 0 rolls\n"
+        );
+        let output = Day04::default().render(input, 1, false).unwrap();
+        assert_eq!(output, "1@1\n1@1\nRound 1: 4 rolls\nNever: 2 rolls\n");
+        let color = Day04::default().render(input, 1, true).unwrap();
+        assert!(color.starts_with("\x1b[38;5;196m1\x1b[0m\x1b[1m@\x1b[0m"));
+    }
+
+    #[test]
+    fn test_frames() {
+        let frames = Day04::default().frames("@@@\n@@@\n").unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].removing, vec![(0, 0), (0, 2), (1, 0), (1, 2)]);
+        assert_eq!(render_grid(&frames[1].grid), ".@.\n.@.\n");
+        assert_eq!(frames[1].removing, vec![(0, 1), (1, 1)]);
+        assert_eq!(render_grid(&frames[2].grid), "...\n...\n");
+        assert!(frames[2].removing.is_empty());
+
+        let mut out = Vec::new();
+        write_animation(&mut out, &frames, Duration::ZERO).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.matches("\x1b[2J").count(), 3);
+        assert!(out.contains("Round 2: removing 2 rolls"));
+        assert!(out.ends_with("Done after 2 rounds\n\x1b[2m.\x1b[0m\x1b[2m.\x1b[0m\x1b[2m.\x1b[0m\n\x1b[2m.\x1b[0m\x1b[2m.\x1b[0m\x1b[2m.\x1b[0m\n"));
+    }
+
+    #[test]
+    fn test_write_ppm() {
+        let frames = Day04::default().frames("@@@\n@@@\n").unwrap();
+        let mut out = Vec::new();
+        write_ppm(&mut out, &frames[1], 2).unwrap();
+        let header = b"P6\n6 4\n255\n";
+        assert_eq!(&out[..header.len()], header);
+        let pixels = &out[header.len()..];
+        assert_eq!(pixels.len(), 6 * 4 * 3);
+        // Top row: empty, removing and empty cells, two pixels each
+        assert_eq!(&pixels[..6], &[255; 6]);
+        assert_eq!(&pixels[6..12], &[220, 40, 40, 220, 40, 40]);
+
+        let dir = std::env::temp_dir().join(format!("day04_frames_{}", std::process::id()));
+        export_ppm_frames(&dir, &frames, 1).unwrap();
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["frame_0000.ppm", "frame_0001.ppm", "frame_0002.ppm"]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_parse_cell() {
         assert_eq!(parse_cell("."), Ok(("", Cell::Empty)));
@@ -211,6 +1040,17 @@ mod tests {
         assert_eq!(grid[1][3], Cell::Empty);
     }
 
+    #[test]
+    fn test_ragged_rows() {
+        let err = Day04::default().part1("@@\n@\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to parse line 2: expected 2 cells, found 1"
+        );
+        assert!(Day04::default().part2("@\n@@").is_err());
+        assert!(Day04::default().render("@@@\n@@\n", 2, false).is_err());
+    }
+
     #[test]
     fn test_count_paper_rolls() {
         let (_, grid) = parse_input_complete(SAMPLE_INPUT).unwrap();
@@ -232,4 +1072,12 @@ mod tests {
             assert_eq!(row.len(), 10, "All rows should have 10 columns");
         }
     }
+
+    #[test]
+    fn test_generate() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let (_, grid) = parse_input_complete(&generate(30, &mut rng)).unwrap();
+        assert_eq!(grid.len(), 30);
+        assert!(grid.iter().all(|row| row.len() == 30));
+    }
 }
@@ -2,9 +2,12 @@ use crate::Solution;
 use nom::{
     IResult, Parser,
     character::complete::{char, newline, u64 as nom_u64},
+    combinator::verify,
     multi::{many1, separated_list1},
     sequence::{separated_pair, terminated},
 };
+use rand::{Rng, rngs::StdRng};
+use tracing::instrument;
 
 pub struct Day05;
 
@@ -35,7 +38,7 @@ fn merge_ranges(sorted_ranges: &[Range]) -> Vec<Range> {
         None => return merged_ranges,
     };
     for &range in iterator {
-        if range.start <= current_range.end + 1 {
+        if range.start <= current_range.end.saturating_add(1) {
             current_range.end = current_range.end.max(range.end);
         } else {
             merged_ranges.push(current_range);
@@ -46,9 +49,120 @@ fn merge_ranges(sorted_ranges: &[Range]) -> Vec<Range> {
     merged_ranges
 }
 
+/// A static interval tree over a list of ranges, answering which of them
+/// contain a number or overlap another range without merging them.
+///
+/// The ranges are kept sorted by start as an implicit balanced tree, where
+/// each node also stores the largest end in its subtree. Counting only needs
+/// the sorted starts and ends: every range ending before `a` also starts
+/// before it, so the ranges overlapping `[a, b]` are those starting at or
+/// before `b` minus those ending before `a`.
+#[derive(Debug, Clone)]
+pub struct IntervalTree {
+    // (index in the original list, range), sorted by start
+    nodes: Vec<(usize, Range)>,
+    max_end: Vec<u64>,
+    starts: Vec<u64>,
+    ends: Vec<u64>,
+}
+
+impl IntervalTree {
+    pub fn new(ranges: &[Range]) -> Self {
+        let mut nodes: Vec<(usize, Range)> = ranges.iter().copied().enumerate().collect();
+        nodes.sort_by_key(|&(_, r)| (r.start, r.end));
+        let mut max_end = vec![0; nodes.len()];
+        Self::build(&nodes, &mut max_end, 0, nodes.len());
+
+        let mut starts: Vec<u64> = ranges.iter().map(|r| r.start).collect();
+        let mut ends: Vec<u64> = ranges.iter().map(|r| r.end).collect();
+        starts.sort_unstable();
+        ends.sort_unstable();
+        Self {
+            nodes,
+            max_end,
+            starts,
+            ends,
+        }
+    }
+
+    // Fills in the largest end of the subtree over nodes[lo..hi], rooted at
+    // its middle, and returns it
+    fn build(nodes: &[(usize, Range)], max_end: &mut [u64], lo: usize, hi: usize) -> u64 {
+        if lo >= hi {
+            return 0;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let left = Self::build(nodes, max_end, lo, mid);
+        let right = Self::build(nodes, max_end, mid + 1, hi);
+        max_end[mid] = nodes[mid].1.end.max(left).max(right);
+        max_end[mid]
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Indices of the ranges containing `number`, ordered by range start.
+    pub fn containing(&self, number: u64) -> Vec<usize> {
+        self.overlapping(Range {
+            start: number,
+            end: number,
+        })
+    }
+
+    /// Number of ranges containing `number`.
+    pub fn count_containing(&self, number: u64) -> usize {
+        self.count_overlapping(Range {
+            start: number,
+            end: number,
+        })
+    }
+
+    /// Indices of the ranges sharing at least one number with `query`,
+    /// ordered by range start.
+    pub fn overlapping(&self, query: Range) -> Vec<usize> {
+        let mut found = Vec::new();
+        self.collect(query, 0, self.nodes.len(), &mut found);
+        found
+    }
+
+    /// Number of ranges sharing at least one number with `query`.
+    pub fn count_overlapping(&self, query: Range) -> usize {
+        let started = self.starts.partition_point(|&s| s <= query.end);
+        let ended = self.ends.partition_point(|&e| e < query.start);
+        started.saturating_sub(ended)
+    }
+
+    fn collect(&self, query: Range, lo: usize, hi: usize, found: &mut Vec<usize>) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        // Nothing below here reaches the query
+        if self.max_end[mid] < query.start {
+            return;
+        }
+        self.collect(query, lo, mid, found);
+        let (index, range) = self.nodes[mid];
+        // This range and everything to its right start after the query
+        if range.start > query.end {
+            return;
+        }
+        if range.end >= query.start {
+            found.push(index);
+        }
+        self.collect(query, mid + 1, hi, found);
+    }
+}
+
 impl Solution for Day05 {
     fn part1(&self, input: &str) -> anyhow::Result<String> {
-        let (_, mut data) = parse_input_complete(input).expect("Failed to parse input");
+        let (_, mut data) = parse_input_complete(input)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input: {}", e))?;
         data.ranges.sort_by_key(|r| r.start);
 
         let merged_ranges = merge_ranges(&data.ranges);
@@ -78,11 +192,16 @@ impl Solution for Day05 {
     }
 
     fn part2(&self, input: &str) -> anyhow::Result<String> {
-        let (_, mut data) = parse_input_complete(input).expect("Failed to parse input");
+        let (_, mut data) = parse_input_complete(input)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input: {}", e))?;
         data.ranges.sort_by_key(|r| r.start);
 
         let merged_ranges = merge_ranges(&data.ranges);
-        let total_covered: u64 = merged_ranges.iter().map(|r| r.end - r.start + 1).sum();
+        // u128: a single range can cover all 2^64 numbers
+        let total_covered: u128 = merged_ranges
+            .iter()
+            .map(|r| (r.end - r.start) as u128 + 1)
+            .sum();
 
         Ok(format!(
             "After merging, there are {} ranges covering a total of {} numbers.",
@@ -92,8 +211,10 @@ impl Solution for Day05 {
     }
 }
 
+// Ranges must not be reversed
 fn parse_range(input: &str) -> IResult<&str, Range> {
-    let (input, (start, end)) = separated_pair(nom_u64, char('-'), nom_u64).parse(input)?;
+    let (input, (start, end)) =
+        verify(separated_pair(nom_u64, char('-'), nom_u64), |(s, e)| s <= e).parse(input)?;
     Ok((input, Range { start, end }))
 }
 
@@ -111,14 +232,122 @@ fn parse_input(input: &str) -> IResult<&str, Input> {
     Ok((input, Input { ranges, numbers }))
 }
 
+#[instrument(name = "parse", level = "debug", skip_all)]
 fn parse_input_complete(input: &str) -> IResult<&str, Input> {
     terminated(parse_input, many1(newline)).parse(input)
 }
 
+/// Entry point for the `parse_day05` fuzz target.
+#[cfg(fuzzing)]
+pub fn fuzz_parse(input: &str) {
+    let _ = parse_input_complete(input);
+}
+
+/// Generates `size` (possibly overlapping) ranges followed by `size` numbers.
+pub fn generate(size: usize, rng: &mut StdRng) -> String {
+    const MAX: u64 = 1_000_000_000_000_000;
+    let mut out = String::new();
+    for _ in 0..size {
+        let start = rng.random_range(1..MAX);
+        let end = start + rng.random_range(0..MAX / size.max(1) as u64);
+        out.push_str(&format!("{}-{}\n", start, end));
+    }
+    out.push('\n');
+    for _ in 0..size {
+        out.push_str(&format!("{}\n", rng.random_range(1..MAX)));
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
     use regex::Regex;
+    use std::collections::HashSet;
+
+    // Reference solution: (numbers inside some range, size of the union)
+    fn naive_solve(data: &Input) -> (usize, usize) {
+        let fresh = data
+            .numbers
+            .iter()
+            .filter(|&&n| data.ranges.iter().any(|r| r.contains(n)))
+            .count();
+        let covered: HashSet<u64> = data.ranges.iter().flat_map(|r| r.start..=r.end).collect();
+        (fresh, covered.len())
+    }
+
+    fn inputs() -> impl Strategy<Value = Input> {
+        let range = (0u64..1000, 0u64..100).prop_map(|(start, width)| Range {
+            start,
+            end: start + width,
+        });
+        (
+            prop::collection::vec(range, 1..10),
+            prop::collection::vec(0u64..1200, 1..20),
+        )
+            .prop_map(|(ranges, numbers)| Input { ranges, numbers })
+    }
+
+    fn format_input(data: &Input) -> String {
+        let mut out = String::new();
+        for r in &data.ranges {
+            out.push_str(&format!("{}-{}\n", r.start, r.end));
+        }
+        out.push('\n');
+        for n in &data.numbers {
+            out.push_str(&format!("{}\n", n));
+        }
+        out
+    }
+
+    proptest! {
+        #[test]
+        fn test_interval_tree_matches_scan(
+            data in inputs(),
+            (a, width) in (0u64..1200, 0u64..50),
+        ) {
+            let tree = IntervalTree::new(&data.ranges);
+            prop_assert_eq!(tree.len(), data.ranges.len());
+            let query = Range { start: a, end: a + width };
+            let scan = |keep: &dyn Fn(&Range) -> bool| {
+                let mut found: Vec<usize> =
+                    (0..data.ranges.len()).filter(|&i| keep(&data.ranges[i])).collect();
+                found.sort_by_key(|&i| (data.ranges[i].start, data.ranges[i].end));
+                found
+            };
+            let overlaps = scan(&|r| r.start <= query.end && r.end >= query.start);
+            prop_assert_eq!(tree.count_overlapping(query), overlaps.len());
+            prop_assert_eq!(tree.overlapping(query), overlaps);
+            for &n in &data.numbers {
+                let containing = scan(&|r| r.contains(n));
+                prop_assert_eq!(tree.count_containing(n), containing.len());
+                prop_assert_eq!(tree.containing(n), containing);
+            }
+        }
+
+        #[test]
+        fn test_part1_matches_reference(data in inputs()) {
+            let (fresh, _) = naive_solve(&data);
+            let output = Day05.part1(&format_input(&data)).unwrap();
+            let expected = format!(
+                "Parsed {} ranges and {} numbers, {} numbers are within at least one range.",
+                data.ranges.len(),
+                data.numbers.len(),
+                fresh
+            );
+            prop_assert_eq!(output, expected);
+        }
+
+        #[test]
+        fn test_part2_matches_reference(data in inputs()) {
+            let (_, covered) = naive_solve(&data);
+            let output = Day05.part2(&format_input(&data)).unwrap();
+            let expected = format!("covering a total of {} numbers.", covered);
+            prop_assert!(output.ends_with(&expected), "{}", output);
+        }
+    }
 
     const SAMPLE_INPUT: &str = "3-5
 10-14
@@ -140,6 +369,22 @@ mod tests {
         assert_eq!(range.end, 5);
     }
 
+    #[test]
+    fn test_parse_range_rejects_reversed() {
+        assert!(parse_range("5-1").is_err());
+        assert!(parse_range("5-5").is_ok());
+        assert!(Day05.part2("5-1\n\n3\n").is_err());
+    }
+
+    #[test]
+    fn test_full_range() {
+        let output = Day05.part2("0-18446744073709551615\n5-18446744073709551615\n\n3\n");
+        assert_eq!(
+            output.unwrap(),
+            "After merging, there are 1 ranges covering a total of 18446744073709551616 numbers."
+        );
+    }
+
     #[test]
     fn test_parse_ranges() {
         let input = "3-5\n10-14\n16-20";
@@ -171,6 +416,29 @@ mod tests {
         assert_eq!(data.numbers[5], 32);
     }
 
+    #[test]
+    fn test_interval_tree() {
+        let (_, data) = parse_input_complete(SAMPLE_INPUT).unwrap();
+        let tree = IntervalTree::new(&data.ranges);
+        assert_eq!(tree.containing(17), vec![3, 2]);
+        assert_eq!(tree.count_containing(17), 2);
+        assert_eq!(tree.containing(5), vec![0]);
+        assert!(tree.containing(8).is_empty());
+        assert_eq!(tree.count_containing(32), 0);
+        let query = Range { start: 5, end: 12 };
+        assert_eq!(tree.overlapping(query), vec![0, 1, 3]);
+        assert_eq!(tree.count_overlapping(query), 3);
+        assert!(IntervalTree::new(&[]).containing(0).is_empty());
+    }
+
+    #[test]
+    fn test_generate() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let (_, data) = parse_input_complete(&generate(40, &mut rng)).unwrap();
+        assert_eq!(data.ranges.len(), 40);
+        assert_eq!(data.numbers.len(), 40);
+    }
+
     #[test]
     fn test_part1_sample() {
         let output = Day05.part1(SAMPLE_INPUT).unwrap();
@@ -1,4 +1,5 @@
 use crate::Solution;
+use rand::{Rng, rngs::StdRng};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Operator {
@@ -21,9 +22,8 @@ pub struct Day06;
 
 impl Solution for Day06 {
     fn part1(&self, input: &str) -> anyhow::Result<String> {
-        let parsed = parsing1::parse_input(input)
-            .expect("Failed to parse input")
-            .1;
+        let (_, parsed) = parsing1::parse_input(input)
+            .map_err(|e| anyhow::anyhow!("Failed to parse input: {}", e))?;
 
         let mut sum = 0u64;
         for column in &parsed.columns {
@@ -42,7 +42,8 @@ impl Solution for Day06 {
     }
 
     fn part2(&self, _input: &str) -> anyhow::Result<String> {
-        let parsed = parsing2::parse_input(_input).expect("Failed to parse input");
+        let parsed = parsing2::parse_input(_input)
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse input"))?;
 
         let mut sum = 0u64;
         for column in &parsed.columns {
@@ -61,15 +62,60 @@ impl Solution for Day06 {
     }
 }
 
+/// Entry point for the `parse_day06` fuzz target.
+#[cfg(fuzzing)]
+pub fn fuzz_parse(input: &str) {
+    let _ = parsing1::parse_input(input);
+    let _ = parsing2::parse_input(input);
+}
+
+/// Generates a worksheet of `size` problems with four numbers each.
+///
+/// Every problem occupies a block of columns as wide as its longest number,
+/// the other numbers are aligned randomly to the left or right within it.
+pub fn generate(size: usize, rng: &mut StdRng) -> String {
+    const ROWS: usize = 4;
+    let mut lines = vec![String::new(); ROWS + 1];
+    for problem in 0..size {
+        if problem > 0 {
+            for line in &mut lines {
+                line.push(' ');
+            }
+        }
+        let width = rng.random_range(1..=4);
+        let widest = rng.random_range(0..ROWS);
+        for (row, line) in lines[..ROWS].iter_mut().enumerate() {
+            let digits = if row == widest {
+                width
+            } else {
+                rng.random_range(1..=width)
+            };
+            let number = rng.random_range(10u64.pow(digits as u32 - 1)..10u64.pow(digits as u32));
+            if rng.random_bool(0.5) {
+                line.push_str(&format!("{:<width$}", number));
+            } else {
+                line.push_str(&format!("{:>width$}", number));
+            }
+        }
+        let operator = if rng.random_bool(0.5) { '+' } else { '*' };
+        lines[ROWS].push_str(&format!("{:<width$}", operator));
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
 mod parsing1 {
     use super::{Column, Input, Operator};
     use nom::{
         IResult, Parser,
         character::complete::{digit1, one_of, space0, space1},
         combinator::map_res,
+        error::{Error, ErrorKind},
         multi::separated_list1,
         sequence::preceded,
     };
+    use tracing::instrument;
     fn parse_number(input: &str) -> IResult<&str, u64> {
         map_res(digit1, |s: &str| s.parse::<u64>()).parse(input)
     }
@@ -92,18 +138,22 @@ mod parsing1 {
         separated_list1(space1, parse_operator).parse(input)
     }
 
+    #[instrument(name = "parse", level = "debug", skip_all)]
     pub fn parse_input(input: &str) -> IResult<&str, Input> {
         let lines: Vec<&str> = input.lines().filter(|l| !l.trim().is_empty()).collect();
+        let Some((operator_line, number_lines)) = lines.split_last() else {
+            return Err(nom::Err::Error(Error::new(input, ErrorKind::Eof)));
+        };
 
         // Parse all number rows except the last line
         let mut all_rows: Vec<Vec<u64>> = Vec::new();
-        for i in 0..lines.len() - 1 {
-            let (_, row) = parse_number_row(lines[i])?;
+        for line in number_lines {
+            let (_, row) = parse_number_row(line)?;
             all_rows.push(row);
         }
 
         // Parse operator row (last line)
-        let (input, operators) = parse_operator_row(lines[lines.len() - 1])?;
+        let (input, operators) = parse_operator_row(operator_line)?;
 
         // Group numbers by column
         let num_cols = operators.len();
@@ -128,15 +178,14 @@ mod parsing1 {
 
 mod parsing2 {
     use super::*;
+    use tracing::instrument;
     type Lines<'input, 'list> = &'list [&'input str];
     pub fn parse_number(input: Lines, col: usize) -> Option<u64> {
         let digits = input[..input.len() - 1]
             .iter()
             .filter_map(|line| {
-                if col >= line.len() {
-                    return None;
-                }
-                let c = line[col..].chars().next()?;
+                // Bytes rather than chars: `col` may fall inside a multi-byte char
+                let c = *line.as_bytes().get(col)? as char;
                 if c.is_digit(10) { Some(c) } else { None }
             })
             .collect::<Vec<char>>();
@@ -149,13 +198,9 @@ mod parsing2 {
     }
     pub fn parse_operator(input: Lines, col: usize) -> Option<Operator> {
         let line = input.last()?;
-        if col >= line.len() {
-            return None;
-        }
-        let c = line[col..].chars().next()?;
-        match c {
-            '*' => Some(Operator::Multiply),
-            '+' => Some(Operator::Add),
+        match line.as_bytes().get(col)? {
+            b'*' => Some(Operator::Multiply),
+            b'+' => Some(Operator::Add),
             _ => None,
         }
     }
@@ -172,6 +217,7 @@ mod parsing2 {
         }
         Some((col, Column { numbers, operator }))
     }
+    #[instrument(name = "parse", level = "debug", skip_all)]
     pub fn parse_input(input: &str) -> Option<Input> {
         let lines: Vec<&str> = input.lines().collect();
         let line_len = lines.first()?.len();
@@ -191,6 +237,68 @@ mod parsing2 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+
+    fn apply(operator: char, numbers: &[u64]) -> u64 {
+        match operator {
+            '+' => numbers.iter().sum(),
+            _ => numbers.iter().product(),
+        }
+    }
+
+    // Reference for part 1: the numbers of a problem are the whitespace
+    // separated words in its position on each line
+    fn naive_total1(input: &str) -> u64 {
+        let lines: Vec<Vec<&str>> = input
+            .lines()
+            .map(|l| l.split_whitespace().collect())
+            .collect();
+        let (operators, rows) = lines.split_last().unwrap();
+        operators
+            .iter()
+            .enumerate()
+            .map(|(i, op)| {
+                let numbers: Vec<u64> = rows.iter().map(|r| r[i].parse().unwrap()).collect();
+                apply(op.chars().next().unwrap(), &numbers)
+            })
+            .sum()
+    }
+
+    // Reference for part 2: transpose the worksheet, so that every problem
+    // is a block of lines separated by blank ones, with the digits of one
+    // number on each line
+    fn naive_total2(input: &str) -> u64 {
+        let lines: Vec<Vec<char>> = input.lines().map(|l| l.chars().collect()).collect();
+        let width = lines.iter().map(Vec::len).max().unwrap();
+        let transposed: Vec<String> = (0..width)
+            .map(|c| lines.iter().map(|l| l.get(c).unwrap_or(&' ')).collect())
+            .collect();
+        transposed
+            .split(|column| column.trim().is_empty())
+            .map(|block| {
+                let operator = block[0].chars().last().unwrap();
+                let numbers: Vec<u64> = block
+                    .iter()
+                    .map(|column| {
+                        let digits: String = column.chars().filter(char::is_ascii_digit).collect();
+                        digits.parse().unwrap()
+                    })
+                    .collect();
+                apply(operator, &numbers)
+            })
+            .sum()
+    }
+
+    proptest! {
+        #[test]
+        fn test_parts_match_reference(seed in any::<u64>(), size in 1usize..20) {
+            let input = generate(size, &mut StdRng::seed_from_u64(seed));
+            let expected = |total: u64| format!("Parsed {} columns, grand total {}", size, total);
+            prop_assert_eq!(Day06.part1(&input).unwrap(), expected(naive_total1(&input)));
+            prop_assert_eq!(Day06.part2(&input).unwrap(), expected(naive_total2(&input)));
+        }
+    }
     const INPUT: &'static str = "123 328  51 64 
  45 64  387 23 
   6 98  215 314
@@ -246,6 +354,17 @@ mod tests {
         assert_eq!(parsed.columns[3].operator, Operator::Add);
     }
 
+    #[test]
+    fn test_generate() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let input = generate(30, &mut rng);
+        let parsed1 = parsing1::parse_input(&input).unwrap().1;
+        assert_eq!(parsed1.columns.len(), 30);
+        assert!(parsed1.columns.iter().all(|c| c.numbers.len() == 4));
+        let parsed2 = parsing2::parse_input(&input).unwrap();
+        assert_eq!(parsed2.columns.len(), 30);
+    }
+
     #[test]
     fn test_part1() {
         let solution = Day06;
@@ -259,4 +378,10 @@ mod tests {
         let result = solution.part2(INPUT).unwrap();
         assert!(result.contains("3263827"));
     }
+
+    #[test]
+    fn test_references_sample() {
+        assert_eq!(naive_total1(INPUT), 4277556);
+        assert_eq!(naive_total2(INPUT), 3263827);
+    }
 }
@@ -1,5 +1,7 @@
 use crate::Solution;
+use rand::{Rng, rngs::StdRng};
 use std::collections::{HashMap, HashSet};
+use tracing::instrument;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Point {
@@ -15,6 +17,7 @@ pub struct Grid {
     pub splitters: HashSet<Point>,
 }
 
+#[instrument(name = "parse", level = "debug", skip_all)]
 fn parse_input(input: &str) -> Grid {
     let lines: Vec<&str> = input.lines().filter(|line| !line.is_empty()).collect();
     let height = lines.len();
@@ -43,6 +46,37 @@ fn parse_input(input: &str) -> Grid {
     }
 }
 
+/// Entry point for the `parse_day07` fuzz target.
+#[cfg(fuzzing)]
+pub fn fuzz_parse(input: &str) {
+    let _ = parse_input(input);
+}
+
+/// Generates a manifold with `size` rows of splitters below the start.
+///
+/// Rows alternate between splitters and empty space, and the grid is wide
+/// enough that no beam can leave it.
+pub fn generate(size: usize, rng: &mut StdRng) -> String {
+    let width = 2 * size + 3;
+    let start = size + 1;
+    let mut out = String::new();
+    for row in 0..=2 * size + 1 {
+        for col in 0..width {
+            let c = if row == 0 && col == start {
+                'S'
+            } else if row % 2 == 0 && row > 0 && col > 0 && col < width - 1 && rng.random_bool(0.2)
+            {
+                '^'
+            } else {
+                '.'
+            };
+            out.push(c);
+        }
+        out.push('\n');
+    }
+    out
+}
+
 pub struct Day07;
 
 impl Solution for Day07 {
@@ -53,13 +87,16 @@ impl Solution for Day07 {
         let mut colset: HashSet<usize> = [grid.start.col].iter().cloned().collect();
 
         let mut num_splittings = 0;
-        while row < grid.height - 1 {
+        while row + 1 < grid.height {
             row += 1;
             let mut new_colset = HashSet::new();
             for &col in &colset {
                 if grid.splitters.contains(&Point { row, col }) {
                     num_splittings += 1;
-                    new_colset.insert(col - 1);
+                    // A beam split off the left edge leaves the grid
+                    if let Some(left) = col.checked_sub(1) {
+                        new_colset.insert(left);
+                    }
                     new_colset.insert(col + 1);
                 } else {
                     new_colset.insert(col);
@@ -84,28 +121,39 @@ impl Solution for Day07 {
 
         let mut row = grid.start.row;
         let mut colset: HashMap<usize, u64> = [(grid.start.col, 1)].iter().cloned().collect();
+        // Timelines whose beam went off the left edge, which no splitter can reach
+        let mut left_grid = 0u64;
+        let overflow = || anyhow::anyhow!("Number of timelines overflows u64");
 
-        while row < grid.height - 1 {
+        while row + 1 < grid.height {
             row += 1;
-            let mut new_colset = HashMap::new();
+            let mut new_colset: HashMap<usize, u64> = HashMap::new();
+            let mut add = |col: Option<usize>, n: u64| {
+                let count = match col {
+                    Some(col) => new_colset.entry(col).or_insert(0),
+                    None => &mut left_grid,
+                };
+                *count = count.checked_add(n).ok_or_else(overflow)?;
+                anyhow::Ok(())
+            };
             for (&col, &n) in &colset {
                 if grid.splitters.contains(&Point { row, col }) {
-                    *new_colset.entry(col - 1).or_insert(0) += n;
-                    *new_colset.entry(col + 1).or_insert(0) += n;
+                    add(col.checked_sub(1), n)?;
+                    add(Some(col + 1), n)?;
                 } else {
-                    *new_colset.entry(col).or_insert(0) += n;
+                    add(Some(col), n)?;
                 }
             }
             colset = new_colset;
         }
+        let timelines = colset
+            .values()
+            .try_fold(left_grid, |total, &n| total.checked_add(n))
+            .ok_or_else(overflow)?;
 
         Ok(format!(
             "Grid size: {}x{}, Start: ({}, {}), Timelines: {}",
-            grid.width,
-            grid.height,
-            grid.start.row,
-            grid.start.col,
-            colset.values().sum::<u64>(),
+            grid.width, grid.height, grid.start.row, grid.start.col, timelines,
         ))
     }
 }
@@ -113,6 +161,86 @@ impl Solution for Day07 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+
+    // Reference for part 1: follow every beam, counting splitters hit
+    fn naive_splittings(grid: &Grid, row: usize, col: usize, hit: &mut HashSet<Point>) {
+        for r in row + 1..grid.height {
+            let p = Point { row: r, col };
+            if grid.splitters.contains(&p) {
+                if hit.insert(p) {
+                    if col > 0 {
+                        naive_splittings(grid, r, col - 1, hit);
+                    }
+                    naive_splittings(grid, r, col + 1, hit);
+                }
+                return;
+            }
+        }
+    }
+
+    // Reference for part 2: enumerate every timeline separately
+    fn naive_timelines(grid: &Grid, row: usize, col: usize) -> u64 {
+        for r in row + 1..grid.height {
+            if grid.splitters.contains(&Point { row: r, col }) {
+                let left = if col > 0 {
+                    naive_timelines(grid, r, col - 1)
+                } else {
+                    1
+                };
+                return left + naive_timelines(grid, r, col + 1);
+            }
+        }
+        1
+    }
+
+    // Small grids, where beams may leave on either side
+    fn grids() -> impl Strategy<Value = String> {
+        (3usize..12, 2usize..12).prop_flat_map(|(width, height)| {
+            (
+                0..width,
+                prop::collection::vec(prop::bool::weighted(0.3), width * height),
+            )
+                .prop_map(move |(start, cells)| {
+                    let mut out = String::new();
+                    for row in 0..height {
+                        for col in 0..width {
+                            out.push(if row == 0 && col == start {
+                                'S'
+                            } else if row > 0 && cells[row * width + col] {
+                                '^'
+                            } else {
+                                '.'
+                            });
+                        }
+                        out.push('\n');
+                    }
+                    out
+                })
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn test_part1_matches_reference(input in grids()) {
+            let grid = parse_input(&input);
+            let mut hit = HashSet::new();
+            naive_splittings(&grid, grid.start.row, grid.start.col, &mut hit);
+            let output = Day07.part1(&input).unwrap();
+            let expected = format!("Splittings: {}", hit.len());
+            prop_assert!(output.ends_with(&expected), "{}", output);
+        }
+
+        #[test]
+        fn test_part2_matches_reference(input in grids()) {
+            let grid = parse_input(&input);
+            let timelines = naive_timelines(&grid, grid.start.row, grid.start.col);
+            let output = Day07.part2(&input).unwrap();
+            let expected = format!("Timelines: {}", timelines);
+            prop_assert!(output.ends_with(&expected), "{}", output);
+        }
+    }
 
     const TEST_INPUT: &str = r#".......S.......
 ...............
@@ -131,6 +259,18 @@ mod tests {
 .^.^.^.^.^...^.
 ...............
 "#;
+    #[test]
+    fn test_beams_leaving_the_grid() {
+        assert!(Day07.part1("S\n^\n").unwrap().ends_with("Splittings: 1"));
+        assert!(Day07.part2("S\n^\n").unwrap().ends_with("Timelines: 2"));
+        // 2^64 timelines
+        let mut input = format!("{}S{}\n", ".".repeat(100), ".".repeat(100));
+        for _ in 0..64 {
+            input.push_str(&format!("{}\n", "^".repeat(201)));
+        }
+        assert!(Day07.part2(&input).is_err());
+    }
+
     #[test]
     fn test_parse_input() {
         let grid = parse_input(TEST_INPUT);
@@ -146,6 +286,15 @@ mod tests {
         assert!(grid.splitters.contains(&Point { row: 4, col: 8 }));
     }
 
+    #[test]
+    fn test_generate() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let grid = parse_input(&generate(20, &mut rng));
+        assert_eq!(grid.width, 43);
+        assert_eq!(grid.height, 42);
+        assert_eq!(grid.start, Point { row: 0, col: 21 });
+    }
+
     #[test]
     fn test_part1() {
         let solution = Day07;
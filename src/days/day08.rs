@@ -4,6 +4,8 @@ use nom::{
     character::complete::{char, line_ending, u64 as nom_u64},
     multi::separated_list1,
 };
+use rand::{Rng, rngs::StdRng};
+use tracing::instrument;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Point3D {
@@ -27,15 +29,35 @@ fn parse_point(input: &str) -> IResult<&str, Point3D> {
         .parse(input)
 }
 
+#[instrument(name = "parse", level = "debug", skip_all)]
 fn parse_points(input: &str) -> IResult<&str, Vec<Point3D>> {
     separated_list1(line_ending, parse_point).parse(input)
 }
 
+/// Entry point for the `parse_day08` fuzz target.
+#[cfg(fuzzing)]
+pub fn fuzz_parse(input: &str) {
+    let _ = parse_points(input);
+}
+
+/// Generates `size` random junction boxes.
+pub fn generate(size: usize, rng: &mut StdRng) -> String {
+    let mut out = String::new();
+    for _ in 0..size {
+        let x = rng.random_range(0..100_000);
+        let y = rng.random_range(0..100_000);
+        let z = rng.random_range(0..100_000);
+        out.push_str(&format!("{},{},{}\n", x, y, z));
+    }
+    out
+}
+
 pub struct Day08;
 
 impl Solution for Day08 {
     fn part1(&self, input: &str) -> anyhow::Result<String> {
-        let (_, points) = parse_points(input).unwrap();
+        let (_, points) =
+            parse_points(input).map_err(|e| anyhow::anyhow!("Failed to parse input: {}", e))?;
         let n = points.len();
         let mut uf = UnionFind::new(n);
 
@@ -61,7 +83,8 @@ impl Solution for Day08 {
     }
 
     fn part2(&self, input: &str) -> anyhow::Result<String> {
-        let (_, points) = parse_points(input).unwrap();
+        let (_, points) =
+            parse_points(input).map_err(|e| anyhow::anyhow!("Failed to parse input: {}", e))?;
         let n = points.len();
         let mut uf = UnionFind::new(n);
 
@@ -146,6 +169,63 @@ impl UnionFind {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+
+    // Reference for `UnionFind`: every element carries the label of its set,
+    // and a union relabels the whole second set
+    struct NaiveSets {
+        label: Vec<usize>,
+    }
+
+    impl NaiveSets {
+        fn union(&mut self, x: usize, y: usize) -> bool {
+            let (from, to) = (self.label[y], self.label[x]);
+            if from == to {
+                return false;
+            }
+            for label in &mut self.label {
+                if *label == from {
+                    *label = to;
+                }
+            }
+            true
+        }
+
+        fn sizes(&self) -> Vec<usize> {
+            let mut sizes = vec![0; self.label.len()];
+            for &label in &self.label {
+                sizes[label] += 1;
+            }
+            sizes.retain(|&size| size > 0);
+            sizes.sort_unstable();
+            sizes
+        }
+    }
+
+    fn unions() -> impl Strategy<Value = (usize, Vec<(usize, usize)>)> {
+        (1usize..20).prop_flat_map(|n| (Just(n), prop::collection::vec((0..n, 0..n), 0..40)))
+    }
+
+    proptest! {
+        #[test]
+        fn test_union_find_matches_reference((n, pairs) in unions()) {
+            let mut uf = UnionFind::new(n);
+            let mut naive = NaiveSets { label: (0..n).collect() };
+            for (x, y) in pairs {
+                prop_assert_eq!(uf.union(x, y), naive.union(x, y));
+                prop_assert!(uf.find(x) == uf.find(y));
+            }
+            let mut sizes = uf.connected_component_sizes();
+            sizes.sort_unstable();
+            prop_assert_eq!(sizes, naive.sizes());
+            for x in 0..n {
+                for y in 0..n {
+                    prop_assert_eq!(uf.find(x) == uf.find(y), naive.label[x] == naive.label[y]);
+                }
+            }
+        }
+    }
 
     const INPUT: &'static str = "162,817,812
 57,618,57
@@ -197,6 +277,13 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_generate() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let (_, points) = parse_points(&generate(50, &mut rng)).unwrap();
+        assert_eq!(points.len(), 50);
+    }
+
     #[test]
     fn test_part1() {
         let solution = Day08;
@@ -2,10 +2,12 @@ use crate::Solution;
 use nom::{
     IResult, Parser,
     character::complete::{char, i64 as nom_i64, line_ending},
-    combinator::map,
+    combinator::{all_consuming, map, verify},
     multi::separated_list1,
     sequence::separated_pair,
 };
+use rand::{Rng, rngs::StdRng};
+use tracing::instrument;
 
 // Tiles are grid squares - the input coordinates representing unit squares on a grid
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -58,22 +60,122 @@ struct Edge {
     p2: Point,
 }
 
+// Largest coordinate accepted, so that no rectangle area overflows an i64
+const MAX_COORDINATE: i64 = 1 << 30;
+
 // Parsing functions
+fn parse_coordinate(input: &str) -> IResult<&str, i64> {
+    verify(nom_i64, |c: &i64| c.abs() <= MAX_COORDINATE).parse(input)
+}
+
 fn parse_tile(input: &str) -> IResult<&str, Tile> {
-    map(separated_pair(nom_i64, char(','), nom_i64), |(x, y)| Tile {
-        x,
-        y,
-    })
+    map(
+        separated_pair(parse_coordinate, char(','), parse_coordinate),
+        |(x, y)| Tile { x, y },
+    )
     .parse(input)
 }
 
-fn parse_input(input: &str) -> Vec<Tile> {
-    let result: IResult<&str, Vec<Tile>> =
-        separated_list1(line_ending, parse_tile).parse(input.trim());
-    match result {
-        Ok((_, tiles)) => tiles,
-        Err(_) => vec![],
+#[instrument(name = "parse", level = "debug", skip_all)]
+fn parse_input(input: &str) -> anyhow::Result<Vec<Tile>> {
+    let (_, tiles) = all_consuming(separated_list1(line_ending, parse_tile))
+        .parse(input.trim())
+        .map_err(|e| anyhow::anyhow!("Failed to parse input: {}", e))?;
+    Ok(tiles)
+}
+
+/// Entry point for the `parse_day09` fuzz target.
+#[cfg(fuzzing)]
+pub fn fuzz_parse(input: &str) {
+    let _ = parse_input(input);
+}
+
+/// Generates a clockwise rectilinear loop made of `size` vertical strips.
+///
+/// Strip `i` spans `x[i]..x[i + 1]` with its own top and bottom edge; all tops
+/// lie above all bottoms so the loop never crosses itself. The first edge is
+/// vertical, as `extract_edges` expects.
+pub fn generate(size: usize, rng: &mut StdRng) -> String {
+    const MID: i64 = 100_000;
+    let strips = size.max(1);
+
+    let mut xs = vec![rng.random_range(0..1000)];
+    for _ in 0..strips {
+        let last = *xs.last().unwrap();
+        xs.push(last + rng.random_range(2..1000));
+    }
+    let mut tops: Vec<i64> = Vec::with_capacity(strips);
+    let mut bottoms: Vec<i64> = Vec::with_capacity(strips);
+    for _ in 0..strips {
+        let top = loop {
+            let y = rng.random_range(0..MID);
+            if tops.last() != Some(&y) {
+                break y;
+            }
+        };
+        let bottom = loop {
+            let y = rng.random_range(MID + 2..2 * MID);
+            if bottoms.last() != Some(&y) {
+                break y;
+            }
+        };
+        tops.push(top);
+        bottoms.push(bottom);
+    }
+
+    let tiles = strip_loop(&xs, &tops, &bottoms);
+
+    let mut out = String::new();
+    for tile in &tiles {
+        out.push_str(&format!("{},{}\n", tile.x, tile.y));
+    }
+    out
+}
+
+// Builds the clockwise loop around strips `xs[i]..xs[i + 1]`, each with its
+// own top and bottom, starting with a vertical edge on the left.
+fn strip_loop(xs: &[i64], tops: &[i64], bottoms: &[i64]) -> Vec<Tile> {
+    let strips = tops.len();
+    let mut tiles = vec![
+        Tile {
+            x: xs[0],
+            y: bottoms[0],
+        },
+        Tile {
+            x: xs[0],
+            y: tops[0],
+        },
+    ];
+    for i in 1..strips {
+        tiles.push(Tile {
+            x: xs[i],
+            y: tops[i - 1],
+        });
+        tiles.push(Tile {
+            x: xs[i],
+            y: tops[i],
+        });
     }
+    tiles.push(Tile {
+        x: xs[strips],
+        y: tops[strips - 1],
+    });
+    tiles.push(Tile {
+        x: xs[strips],
+        y: bottoms[strips - 1],
+    });
+    for i in (1..strips).rev() {
+        tiles.push(Tile {
+            x: xs[i],
+            y: bottoms[i],
+        });
+        tiles.push(Tile {
+            x: xs[i],
+            y: bottoms[i - 1],
+        });
+    }
+
+    tiles
 }
 
 // Part 1: Maximum rectangle from any two tiles
@@ -92,36 +194,32 @@ fn solve_part1(tiles: &[Tile]) -> i64 {
 
 // Part 2: Helper functions
 
-// Determine direction from tile t1 to tile t2
-fn direction(t1: Tile, t2: Tile) -> Direction {
+// Determine direction from tile t1 to tile t2, if they differ in exactly
+// one coordinate
+fn direction(t1: Tile, t2: Tile) -> Option<Direction> {
     let dx = t2.x - t1.x;
     let dy = t2.y - t1.y;
 
-    // Assert that tiles are adjacent (differ in exactly one coordinate)
-    assert!(
-        (dx == 0) as i64 + (dy == 0) as i64 == 1,
-        "Tiles must be adjacent: {:?} -> {:?}",
-        t1,
-        t2
-    );
-
-    if dx < 0 {
-        Direction::West
+    if (dx == 0) == (dy == 0) {
+        None
+    } else if dx < 0 {
+        Some(Direction::West)
     } else if dx > 0 {
-        Direction::East
+        Some(Direction::East)
     } else if dy < 0 {
-        Direction::North
+        Some(Direction::North)
     } else {
-        Direction::South
+        Some(Direction::South)
     }
 }
 
-// Compute corner Point from a Tile and the directions before and after
-fn get_corner(d0: Direction, tile: Tile, d2: Direction) -> Point {
+// Compute corner Point from a Tile and the directions before and after,
+// if the loop turns there
+fn get_corner(d0: Direction, tile: Tile, d2: Direction) -> Option<Point> {
     let x = tile.x;
     let y = tile.y;
 
-    match (d0, d2) {
+    Some(match (d0, d2) {
         // Convex corners
         (Direction::North, Direction::East) => Point { x, y },
         (Direction::East, Direction::South) => Point { x: x + 1, y },
@@ -134,32 +232,40 @@ fn get_corner(d0: Direction, tile: Tile, d2: Direction) -> Point {
         (Direction::North, Direction::West) => Point { x, y: y + 1 },
         (Direction::West, Direction::South) => Point { x: x + 1, y: y + 1 },
 
-        _ => panic!("Invalid direction combination: {:?} -> {:?}", d0, d2),
-    }
+        _ => return None,
+    })
 }
 
 // Convert tiles to corner Points
-fn compute_corners(tiles: &[Tile]) -> Vec<Point> {
+fn compute_corners(tiles: &[Tile]) -> anyhow::Result<Vec<Point>> {
     let n = tiles.len();
     let mut corners = Vec::with_capacity(n);
 
-    for i in 0..n {
-        let p0 = tiles[(i + n - 1) % n];
-        let p1 = tiles[i];
-        let p2 = tiles[(i + 1) % n];
-
-        let d0 = direction(p0, p1);
-        let d2 = direction(p1, p2);
-
-        corners.push(get_corner(d0, p1, d2));
+    let direction_to = |i: usize| {
+        let (t1, t2) = (tiles[i], tiles[(i + 1) % n]);
+        direction(t1, t2).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Tiles {:?} and {:?} are not on a common row or column",
+                t1,
+                t2
+            )
+        })
+    };
+    for (i, &p1) in tiles.iter().enumerate() {
+        let d0 = direction_to((i + n - 1) % n)?;
+        let d2 = direction_to(i)?;
+
+        let corner = get_corner(d0, p1, d2)
+            .ok_or_else(|| anyhow::anyhow!("The loop does not turn at tile {:?}", p1))?;
+        corners.push(corner);
     }
 
-    corners
+    Ok(corners)
 }
 
-// Extract vertical and horizontal edges from corner Points
+// Extract vertical and horizontal edges from corner Points. The loop turns
+// at every corner, so its edges alternate, and the first one must be vertical.
 fn extract_edges(corners: &[Point]) -> (Vec<Edge>, Vec<Edge>) {
-    // TODO: This assumes that the first edge is vertical. Could be made more robust.
     let n = corners.len();
     let mut verticals = Vec::with_capacity(n / 2);
     let mut horizontals = Vec::with_capacity(n / 2);
@@ -223,8 +329,17 @@ fn touch_horizontal(rect: &Rect, edge: &Edge) -> bool {
 }
 
 // Part 2: Maximum rectangle not intersecting carpet boundary
-fn solve_part2(tiles: &[Tile]) -> i64 {
-    let corners = compute_corners(tiles);
+fn solve_part2(tiles: &[Tile]) -> anyhow::Result<i64> {
+    anyhow::ensure!(
+        tiles.len() >= 4,
+        "A loop needs at least 4 tiles, not {}",
+        tiles.len()
+    );
+    let mut corners = compute_corners(tiles)?;
+    // Start with a vertical edge
+    if corners[0].x != corners[1].x {
+        corners.rotate_left(1);
+    }
     let (verticals, horizontals) = extract_edges(&corners);
 
     let mut max_area = 0;
@@ -243,21 +358,21 @@ fn solve_part2(tiles: &[Tile]) -> i64 {
         }
     }
 
-    max_area
+    Ok(max_area)
 }
 
 pub struct Day09;
 
 impl Solution for Day09 {
     fn part1(&self, input: &str) -> anyhow::Result<String> {
-        let tiles = parse_input(input);
+        let tiles = parse_input(input)?;
         let max_area = solve_part1(&tiles);
         Ok(format!("Maximum rectangle area: {}", max_area))
     }
 
     fn part2(&self, input: &str) -> anyhow::Result<String> {
-        let tiles = parse_input(input);
-        let max_area = solve_part2(&tiles);
+        let tiles = parse_input(input)?;
+        let max_area = solve_part2(&tiles)?;
         Ok(format!(
             "Maximum non-intersecting rectangle area: {}",
             max_area
@@ -268,6 +383,76 @@ impl Solution for Day09 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
+
+    // Reference for part 2: rasterise the loop, flood fill the outside and
+    // check every tile of every candidate rectangle
+    fn naive_part2(tiles: &[Tile]) -> i64 {
+        let mut boundary = HashSet::new();
+        for i in 0..tiles.len() {
+            let (a, b) = (tiles[i], tiles[(i + 1) % tiles.len()]);
+            for x in a.x.min(b.x)..=a.x.max(b.x) {
+                for y in a.y.min(b.y)..=a.y.max(b.y) {
+                    boundary.insert((x, y));
+                }
+            }
+        }
+        let min_x = tiles.iter().map(|t| t.x).min().unwrap() - 1;
+        let max_x = tiles.iter().map(|t| t.x).max().unwrap() + 1;
+        let min_y = tiles.iter().map(|t| t.y).min().unwrap() - 1;
+        let max_y = tiles.iter().map(|t| t.y).max().unwrap() + 1;
+
+        let mut outside = HashSet::from([(min_x, min_y)]);
+        let mut stack = vec![(min_x, min_y)];
+        while let Some((x, y)) = stack.pop() {
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                let in_box = (min_x..=max_x).contains(&nx) && (min_y..=max_y).contains(&ny);
+                if in_box && !boundary.contains(&(nx, ny)) && outside.insert((nx, ny)) {
+                    stack.push((nx, ny));
+                }
+            }
+        }
+
+        let mut max_area = 0;
+        for (i, &t1) in tiles.iter().enumerate() {
+            for &t2 in &tiles[i + 1..] {
+                let inside = (t1.x.min(t2.x)..=t1.x.max(t2.x))
+                    .all(|x| (t1.y.min(t2.y)..=t1.y.max(t2.y)).all(|y| !outside.contains(&(x, y))));
+                if inside {
+                    max_area = max_area.max(Rect::from_tiles(t1, t2).area());
+                }
+            }
+        }
+        max_area
+    }
+
+    // Small strip loops as built by `generate`
+    fn loops() -> impl Strategy<Value = Vec<Tile>> {
+        prop::collection::vec((2i64..5, 0i64..6, 7i64..13), 1..6)
+            .prop_filter("neighbouring strips need different heights", |strips| {
+                strips
+                    .windows(2)
+                    .all(|w| w[0].1 != w[1].1 && w[0].2 != w[1].2)
+            })
+            .prop_map(|strips| {
+                let mut xs = vec![0];
+                for &(gap, _, _) in &strips {
+                    xs.push(xs.last().unwrap() + gap);
+                }
+                let tops: Vec<i64> = strips.iter().map(|s| s.1).collect();
+                let bottoms: Vec<i64> = strips.iter().map(|s| s.2).collect();
+                strip_loop(&xs, &tops, &bottoms)
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn test_part2_matches_reference(tiles in loops()) {
+            prop_assert_eq!(solve_part2(&tiles).unwrap(), naive_part2(&tiles));
+        }
+    }
 
     const SAMPLE_INPUT: &str = "7,1
 11,1
@@ -286,7 +471,7 @@ mod tests {
 
     #[test]
     fn test_parse_input() {
-        let tiles = parse_input(SAMPLE_INPUT);
+        let tiles = parse_input(SAMPLE_INPUT).unwrap();
         assert_eq!(tiles.len(), 8);
         assert_eq!(tiles[0].x, 7);
         assert_eq!(tiles[0].y, 1);
@@ -321,16 +506,16 @@ mod tests {
     fn test_direction() {
         let t1 = Tile { x: 5, y: 3 };
         let t2 = Tile { x: 6, y: 3 };
-        assert_eq!(direction(t1, t2), Direction::East);
+        assert_eq!(direction(t1, t2), Some(Direction::East));
 
         let t3 = Tile { x: 5, y: 2 };
-        assert_eq!(direction(t1, t3), Direction::North);
+        assert_eq!(direction(t1, t3), Some(Direction::North));
 
         let t4 = Tile { x: 4, y: 3 };
-        assert_eq!(direction(t1, t4), Direction::West);
+        assert_eq!(direction(t1, t4), Some(Direction::West));
 
         let t5 = Tile { x: 5, y: 4 };
-        assert_eq!(direction(t1, t5), Direction::South);
+        assert_eq!(direction(t1, t5), Some(Direction::South));
     }
 
     #[test]
@@ -338,23 +523,35 @@ mod tests {
         let tile = Tile { x: 5, y: 3 };
 
         // Convex corner: North -> East
-        let corner = get_corner(Direction::North, tile, Direction::East);
+        let corner = get_corner(Direction::North, tile, Direction::East).unwrap();
         assert_eq!(corner.x, 5);
         assert_eq!(corner.y, 3);
 
         // Convex corner: East -> South
-        let corner = get_corner(Direction::East, tile, Direction::South);
+        let corner = get_corner(Direction::East, tile, Direction::South).unwrap();
         assert_eq!(corner.x, 6);
         assert_eq!(corner.y, 3);
     }
 
     #[test]
     fn test_compute_corners() {
-        let tiles = parse_input(SAMPLE_INPUT);
-        let corners = compute_corners(&tiles);
+        let tiles = parse_input(SAMPLE_INPUT).unwrap();
+        let corners = compute_corners(&tiles).unwrap();
         assert_eq!(corners.len(), tiles.len());
     }
 
+    #[test]
+    fn test_generate() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let tiles = parse_input(&generate(10, &mut rng)).unwrap();
+        assert_eq!(tiles.len(), 40);
+        // Fails if consecutive tiles are not a proper turn
+        let corners = compute_corners(&tiles).unwrap();
+        let (verticals, horizontals) = extract_edges(&corners);
+        assert!(verticals.iter().all(|e| e.p1.x == e.p2.x));
+        assert!(horizontals.iter().all(|e| e.p1.y == e.p2.y));
+    }
+
     #[test]
     fn test_part1() {
         let solution = Day09;
@@ -375,5 +572,20 @@ mod tests {
 2,3";
         let result = solution.part2(input).unwrap();
         assert_eq!(result, "Maximum non-intersecting rectangle area: 24");
+        // Starting with a horizontal edge
+        let result = solution.part2(SAMPLE_INPUT).unwrap();
+        assert_eq!(result, "Maximum non-intersecting rectangle area: 24");
+    }
+
+    #[test]
+    fn test_part2_rejects_broken_loops() {
+        let solution = Day09;
+        assert!(solution.part2("1,1\n").is_err());
+        assert!(solution.part2("1,2").is_err());
+        // Diagonal step
+        assert!(solution.part2("0,0\n2,0\n2,2\n1,1\n").is_err());
+        // No turn at 1,0
+        assert!(solution.part2("0,0\n1,0\n2,0\n2,2\n0,2\n").is_err());
+        assert!(solution.part1("0,0\n2000000000,0\n").is_err());
     }
 }
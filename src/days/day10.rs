@@ -2,6 +2,8 @@ use crate::Solution;
 use good_lp::{
     self, Solution as LpSolution, SolverModel, constraint, default_solver, variable, variables,
 };
+use rand::{Rng, rngs::StdRng};
+use tracing::{instrument, warn};
 
 fn group_to_bitmask(group: &Vec<usize>) -> u16 {
     group.iter().fold(0, |acc, &n| acc | (1 << n))
@@ -102,7 +104,7 @@ impl Solution for Day10 {
             if let Ok(min_joltage) = solve_device_milp(device) {
                 total += min_joltage;
             } else {
-                println!("Failed to solve device {}", i);
+                warn!("Failed to solve device {}", i);
             }
         }
         Ok(format!(
@@ -113,6 +115,58 @@ impl Solution for Day10 {
     }
 }
 
+/// Entry point for the `parse_day10` fuzz target.
+#[cfg(fuzzing)]
+pub fn fuzz_parse(input: &str) {
+    let _ = parse_input(input);
+}
+
+/// Generates `size` devices that are solvable in both parts: the light
+/// pattern is the XOR of some of the groups, and the joltages are reached by
+/// pressing each group a random number of times.
+pub fn generate(size: usize, rng: &mut StdRng) -> String {
+    let mut out = String::new();
+    for _ in 0..size {
+        let num_lights = rng.random_range(3..=10);
+        let num_groups = rng.random_range(3..=12);
+        let groups: Vec<Vec<usize>> = (0..num_groups)
+            .map(|_| {
+                let group: Vec<usize> = (0..num_lights).filter(|_| rng.random_bool(0.4)).collect();
+                if group.is_empty() {
+                    vec![rng.random_range(0..num_lights)]
+                } else {
+                    group
+                }
+            })
+            .collect();
+
+        let mut pattern = vec![false; num_lights];
+        let mut joltages = vec![0u32; num_lights];
+        for group in &groups {
+            if rng.random_bool(0.5) {
+                for &light in group {
+                    pattern[light] = !pattern[light];
+                }
+            }
+            let presses = rng.random_range(0..=20);
+            for &light in group {
+                joltages[light] += presses;
+            }
+        }
+
+        out.push('[');
+        out.extend(pattern.iter().map(|&on| if on { '#' } else { '.' }));
+        out.push(']');
+        for group in &groups {
+            let lights: Vec<String> = group.iter().map(|l| l.to_string()).collect();
+            out.push_str(&format!(" ({})", lights.join(",")));
+        }
+        let joltages: Vec<String> = joltages.iter().map(|j| j.to_string()).collect();
+        out.push_str(&format!(" {{{}}}\n", joltages.join(",")));
+    }
+    out
+}
+
 mod parser {
     use super::Device;
     use nom::{
@@ -172,6 +226,7 @@ mod parser {
     }
 }
 
+#[instrument(name = "parse", level = "debug", skip_all)]
 fn parse_input(input: &str) -> Vec<Device> {
     input
         .lines()
@@ -183,6 +238,126 @@ fn parse_input(input: &str) -> Vec<Device> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+
+    // Reference for part 1: try every subset of groups
+    fn naive_min_presses(device: &Device) -> Option<u32> {
+        let target = pattern_to_bitmask(&device.pattern);
+        (0u32..1 << device.groups.len())
+            .filter(|subset| {
+                let lights = device
+                    .groups
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, _)| subset & (1 << i) != 0)
+                    .fold(0, |acc, (_, g)| acc ^ group_to_bitmask(g));
+                lights == target
+            })
+            .map(|subset| subset.count_ones())
+            .min()
+    }
+
+    // Reference for part 2: try every number of presses up to the largest
+    // joltage for each group
+    fn naive_min_joltage_presses(device: &Device) -> Option<u32> {
+        let max = device.joltages.iter().copied().max().unwrap_or(0);
+        let mut presses = vec![0u32; device.groups.len()];
+        let mut best = None;
+        loop {
+            let mut joltages = vec![0; device.joltages.len()];
+            for (group, &n) in device.groups.iter().zip(&presses) {
+                for &light in group {
+                    joltages[light] += n;
+                }
+            }
+            if joltages == device.joltages {
+                let total = presses.iter().sum::<u32>();
+                best = Some(best.map_or(total, |b: u32| b.min(total)));
+            }
+            // Next combination, counting in base max + 1
+            let Some(i) = presses.iter().position(|&n| n < max) else {
+                return best;
+            };
+            presses[i] += 1;
+            presses[..i].fill(0);
+        }
+    }
+
+    // Small devices reaching their joltages by pressing each group 0 to 3 times
+    fn joltage_devices() -> impl Strategy<Value = Device> {
+        (1usize..=4).prop_flat_map(|num_lights| {
+            prop::collection::vec(
+                (
+                    prop::sample::subsequence((0..num_lights).collect::<Vec<_>>(), 1..=num_lights),
+                    0u32..4,
+                ),
+                1..=4,
+            )
+            .prop_map(move |groups| {
+                let mut joltages = vec![0; num_lights];
+                for (group, presses) in &groups {
+                    for &light in group {
+                        joltages[light] += presses;
+                    }
+                }
+                Device {
+                    pattern: vec![false; num_lights],
+                    groups: groups.into_iter().map(|(group, _)| group).collect(),
+                    joltages,
+                }
+            })
+        })
+    }
+
+    fn devices() -> impl Strategy<Value = Device> {
+        (1usize..=6).prop_flat_map(|num_lights| {
+            (
+                prop::collection::vec(any::<bool>(), num_lights),
+                prop::collection::vec(
+                    prop::sample::subsequence((0..num_lights).collect::<Vec<_>>(), 1..=num_lights),
+                    1..=8,
+                ),
+            )
+                .prop_map(|(pattern, groups)| Device {
+                    joltages: vec![0; pattern.len()],
+                    pattern,
+                    groups,
+                })
+        })
+    }
+
+    fn format_device(device: &Device) -> String {
+        let mut out = String::from("[");
+        out.extend(device.pattern.iter().map(|&on| if on { '#' } else { '.' }));
+        out.push(']');
+        for group in &device.groups {
+            let lights: Vec<String> = group.iter().map(|l| l.to_string()).collect();
+            out.push_str(&format!(" ({})", lights.join(",")));
+        }
+        let joltages: Vec<String> = device.joltages.iter().map(|j| j.to_string()).collect();
+        out.push_str(&format!(" {{{}}}\n", joltages.join(",")));
+        out
+    }
+
+    proptest! {
+        #[test]
+        fn test_min_repr_pattern_matches_reference(device in devices()) {
+            let parsed = parse_input(&format_device(&device));
+            prop_assert_eq!(parsed.len(), 1);
+            let g = parsed[0].groups.iter().map(group_to_bitmask).collect();
+            let min_size = min_repr_pattern(pattern_to_bitmask(&parsed[0].pattern), &g);
+            let expected = naive_min_presses(&device).map_or(u16::MAX, |n| n as u16);
+            prop_assert_eq!(min_size, expected);
+        }
+
+        #[test]
+        fn test_milp_matches_reference(device in joltage_devices()) {
+            let expected = naive_min_joltage_presses(&device);
+            prop_assert!(expected.is_some());
+            prop_assert_eq!(solve_device_milp(&device).ok(), expected);
+        }
+    }
 
     const SAMPLE_INPUT: &str = "\
 [.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,7}
@@ -218,6 +393,18 @@ mod tests {
         assert_eq!(devices[2].joltages, vec![10, 11, 11, 5, 10, 5]);
     }
 
+    #[test]
+    fn test_generate() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let devices = parse_input(&generate(20, &mut rng));
+        assert_eq!(devices.len(), 20);
+        for device in &devices {
+            let g = device.groups.iter().map(group_to_bitmask).collect();
+            let min_size = min_repr_pattern(pattern_to_bitmask(&device.pattern), &g);
+            assert_ne!(min_size, u16::MAX);
+        }
+    }
+
     #[test]
     fn test_part1_sample() {
         let day10 = Day10;
@@ -1,33 +1,34 @@
 use crate::Solution;
+use crate::graph::{Graph, GraphError};
 use nom::{
     IResult, Parser,
-    bytes::complete::take,
-    character::complete::{char, line_ending, space1},
+    character::complete::{alphanumeric1, char, line_ending, space1},
     combinator::map,
     multi::separated_list1,
     sequence::separated_pair,
 };
-use std::collections::HashMap;
+use rand::{Rng, rngs::StdRng, seq::SliceRandom};
+use std::collections::HashSet;
+use tracing::{debug, instrument};
 
-type NodeId = [u8; 3];
 #[derive(Debug, Clone)]
-struct Connection {
-    node: NodeId,
-    neighbors: Vec<NodeId>,
+struct Connection<'a> {
+    node: &'a str,
+    neighbors: Vec<&'a str>,
 }
 
-// Parse a 3-character identifier
-fn parse_identifier(input: &str) -> IResult<&str, [u8; 3]> {
-    map(take(3usize), |s: &str| s.as_bytes().try_into().unwrap()).parse(input)
+// Parse a node identifier, e.g. "abc"
+fn parse_identifier(input: &str) -> IResult<&str, &str> {
+    alphanumeric1(input)
 }
 
 // Parse a list of space-separated identifiers
-fn parse_neighbor_list(input: &str) -> IResult<&str, Vec<[u8; 3]>> {
+fn parse_neighbor_list(input: &str) -> IResult<&str, Vec<&str>> {
     separated_list1(space1, parse_identifier).parse(input)
 }
 
 // Parse a single line: "abc: def ghi jkl"
-fn parse_connection(input: &str) -> IResult<&str, Connection> {
+fn parse_connection(input: &str) -> IResult<&str, Connection<'_>> {
     map(
         separated_pair(parse_identifier, (char(':'), space1), parse_neighbor_list),
         |(node, neighbors)| Connection { node, neighbors },
@@ -36,63 +37,115 @@ fn parse_connection(input: &str) -> IResult<&str, Connection> {
 }
 
 // Parse the full input
-fn parse_input(input: &str) -> IResult<&str, Vec<Connection>> {
+#[instrument(name = "parse", level = "debug", skip_all)]
+fn parse_input(input: &str) -> IResult<&str, Vec<Connection<'_>>> {
     separated_list1(line_ending, parse_connection).parse(input)
 }
 
-struct PathSolver {
-    // Graph: node -> list of neighbors
-    connections: HashMap<NodeId, Vec<NodeId>>,
-    // Cache: (from, to) -> number of paths
-    cache: HashMap<(NodeId, NodeId), u64>,
+fn build_graph(connections: &[Connection]) -> Graph {
+    let mut graph = Graph::new();
+    for conn in connections {
+        graph.add_node(conn.node);
+        for neighbor in &conn.neighbors {
+            graph.add_edge(conn.node, neighbor);
+        }
+    }
+    debug!(
+        "Built graph with {} nodes and {} edges",
+        graph.len(),
+        graph.num_edges()
+    );
+    graph
 }
 
-impl PathSolver {
-    fn new(connections_list: Vec<Connection>) -> Self {
-        let mut connections = HashMap::new();
-        for conn in connections_list {
-            connections.insert(conn.node, conn.neighbors);
-        }
-        Self {
-            connections,
-            cache: HashMap::new(),
-        }
+// Number of paths between two named nodes; a missing node has no paths
+fn num_paths(graph: &Graph, a: &str, b: &str) -> anyhow::Result<u64> {
+    match (graph.node(a), graph.node(b)) {
+        (Some(a), Some(b)) => Ok(graph.count_paths(a, b)?),
+        _ => Ok(0),
     }
+}
 
-    fn num_paths(&mut self, a: NodeId, b: NodeId) -> u64 {
-        // Base case
-        if a == b {
-            return 1;
-        }
+/// Entry point for the `parse_day11` fuzz target.
+#[cfg(fuzzing)]
+pub fn fuzz_parse(input: &str) {
+    let _ = parse_input(input);
+}
 
-        // Check cache
-        if let Some(&count) = self.cache.get(&(a, b)) {
-            return count;
+/// Generates a DAG with `size` devices besides the named ones.
+///
+/// Devices are placed in a random topological order with "svr" first, "you"
+/// early, "dac" and "fft" in the middle and "out" last. Every device links to
+/// one or two of the next few devices, so every path ends at "out".
+pub fn generate(size: usize, rng: &mut StdRng) -> String {
+    const WINDOW: usize = 30;
+    let mut used: HashSet<String> = ["svr", "you", "dac", "fft", "out"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let mut names = Vec::with_capacity(size + 5);
+    while names.len() < size {
+        let name: String = (0..3)
+            .map(|_| char::from(b'a' + rng.random_range(0..26)))
+            .collect();
+        if used.insert(name.clone()) {
+            names.push(name);
         }
+    }
 
-        // Recursive case: collect neighbors first to avoid borrow issues
-        let neighbors = self.connections.get(&a).cloned();
-        let result = if let Some(neighbors) = neighbors {
-            neighbors
-                .iter()
-                .map(|&neighbor| self.num_paths(neighbor, b))
-                .sum()
-        } else {
-            0 // Dead end: no outgoing edges
-        };
+    let mut middle = vec!["dac".to_string(), "fft".to_string()];
+    middle.shuffle(rng);
+    let quarter = names.len() / 4;
+    let first = names.len() / 2;
+    let second = (first + rng.random_range(0..WINDOW)).min(names.len());
+    names.insert(second, middle.pop().unwrap());
+    names.insert(first, middle.pop().unwrap());
+    names.insert(rng.random_range(0..=quarter), "you".to_string());
+    names.insert(0, "svr".to_string());
+    names.push("out".to_string());
 
-        self.cache.insert((a, b), result);
-        result
+    // A spine from "svr" through both middle devices to "out" makes sure
+    // part 2 has at least one path
+    let last = names.len() - 1;
+    let mut stops: Vec<usize> = ["dac", "fft", "out"]
+        .iter()
+        .map(|name| names.iter().position(|n| n == name).unwrap())
+        .collect();
+    stops.sort();
+    let mut spine_next = vec![None; names.len()];
+    let mut current = 0;
+    while current < last {
+        let stop = *stops.iter().find(|&&s| s > current).unwrap();
+        let next = (current + rng.random_range(1..=WINDOW)).min(stop);
+        spine_next[current] = Some(next);
+        current = next;
     }
+
+    let mut out = String::new();
+    for (i, name) in names.iter().enumerate().take(last) {
+        let window_end = (i + WINDOW).min(last);
+        let mut targets: Vec<usize> = (0..rng.random_range(1..=2))
+            .map(|_| rng.random_range(i + 1..=window_end))
+            .collect();
+        if let Some(next) = spine_next[i] {
+            targets[0] = next;
+        }
+        targets.sort();
+        targets.dedup();
+        let targets: Vec<&str> = targets.iter().map(|&t| names[t].as_str()).collect();
+        out.push_str(&format!("{}: {}\n", name, targets.join(" ")));
+    }
+    out
 }
 
 pub struct Day11;
 impl Solution for Day11 {
     fn part1(&self, input: &str) -> anyhow::Result<String> {
-        let (_, connections) = parse_input(input).expect("Failed to parse input");
-        let mut solver = PathSolver::new(connections);
+        let (_, connections) =
+            parse_input(input).map_err(|e| anyhow::anyhow!("Failed to parse input: {}", e))?;
+        let graph = build_graph(&connections);
 
-        let result = solver.num_paths(*b"you", *b"out");
+        let result = num_paths(&graph, "you", "out")?;
 
         Ok(format!(
             "Number of distinct paths from 'you' to 'out': {}",
@@ -101,17 +154,172 @@ impl Solution for Day11 {
     }
 
     fn part2(&self, input: &str) -> anyhow::Result<String> {
-        let (_, connections) = parse_input(input).expect("Failed to parse input");
-        let mut solver = PathSolver::new(connections);
+        let (_, connections) =
+            parse_input(input).map_err(|e| anyhow::anyhow!("Failed to parse input: {}", e))?;
+        let graph = build_graph(&connections);
 
-        let y2d = solver.num_paths(*b"svr", *b"dac");
-        let d2f = solver.num_paths(*b"dac", *b"fft");
-        let f2o = solver.num_paths(*b"fft", *b"out");
-        let y2f = solver.num_paths(*b"svr", *b"fft");
-        let f2d = solver.num_paths(*b"fft", *b"dac");
-        let d2o = solver.num_paths(*b"dac", *b"out");
-        let result = y2d * d2f * f2o + y2f * f2d * d2o;
+        let y2d = num_paths(&graph, "svr", "dac")?;
+        let d2f = num_paths(&graph, "dac", "fft")?;
+        let f2o = num_paths(&graph, "fft", "out")?;
+        let y2f = num_paths(&graph, "svr", "fft")?;
+        let f2d = num_paths(&graph, "fft", "dac")?;
+        let d2o = num_paths(&graph, "dac", "out")?;
+        let through = |a: u64, b: u64, c: u64| a.checked_mul(b)?.checked_mul(c);
+        let result = through(y2d, d2f, f2o)
+            .zip(through(y2f, f2d, d2o))
+            .and_then(|(a, b)| a.checked_add(b))
+            .ok_or(GraphError::Overflow)?;
 
         Ok(format!("Paths going through 'dac' and 'fft': {}", result))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+
+    // Reference solution walking every path from `node` to "out", counting
+    // those that visit all of `required`
+    fn naive_paths(graph: &Graph, node: &str, required: &[&str], seen: usize) -> u64 {
+        let seen = seen + required.iter().filter(|&&r| r == node).count();
+        if node == "out" {
+            return (seen == required.len()) as u64;
+        }
+        let Some(id) = graph.node(node) else {
+            return 0;
+        };
+        graph
+            .neighbors(id)
+            .iter()
+            .map(|&next| naive_paths(graph, graph.name(next), required, seen))
+            .sum()
+    }
+
+    // Random DAGs on the named devices plus a few others, in a random order
+    fn dags() -> impl Strategy<Value = String> {
+        let names = vec!["svr", "you", "dac", "fft", "aaa", "bbb", "ccc", "ddd"];
+        (
+            Just(names).prop_shuffle(),
+            prop::collection::vec(prop::bool::weighted(0.4), 81),
+        )
+            .prop_map(|(mut names, edges)| {
+                names.push("out");
+                let mut out = String::new();
+                for (i, name) in names.iter().enumerate() {
+                    let targets: Vec<&str> = (i + 1..names.len())
+                        .filter(|&j| edges[i * names.len() + j])
+                        .map(|j| names[j])
+                        .collect();
+                    if !targets.is_empty() {
+                        out.push_str(&format!("{}: {}\n", name, targets.join(" ")));
+                    }
+                }
+                out
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn test_part1_matches_reference(input in dags()) {
+            prop_assume!(!input.is_empty());
+            let (_, connections) = parse_input(&input).unwrap();
+            let graph = build_graph(&connections);
+            let expected = naive_paths(&graph, "you", &[], 0);
+            let output = Day11.part1(&input).unwrap();
+            prop_assert_eq!(output, format!("Number of distinct paths from 'you' to 'out': {}", expected));
+        }
+
+        #[test]
+        fn test_part2_matches_reference(input in dags()) {
+            prop_assume!(!input.is_empty());
+            let (_, connections) = parse_input(&input).unwrap();
+            let graph = build_graph(&connections);
+            let expected = naive_paths(&graph, "svr", &["dac", "fft"], 0);
+            let output = Day11.part2(&input).unwrap();
+            prop_assert_eq!(output, format!("Paths going through 'dac' and 'fft': {}", expected));
+        }
+    }
+
+    const SAMPLE_INPUT_1: &str = "aaa: you hhh
+you: bbb ccc
+bbb: ddd eee
+ccc: ddd eee fff
+ddd: ggg
+eee: out
+fff: out
+ggg: out
+hhh: ccc fff iii
+iii: out
+";
+
+    const SAMPLE_INPUT_2: &str = "svr: aaa bbb
+aaa: fft
+fft: ccc
+bbb: tty
+tty: ccc
+ccc: ddd eee
+ddd: hub
+hub: fff
+eee: dac
+dac: fff
+fff: ggg hhh
+ggg: out
+hhh: out
+";
+
+    #[test]
+    fn test_parse_input() {
+        let (_, connections) = parse_input(SAMPLE_INPUT_1).unwrap();
+        assert_eq!(connections.len(), 10);
+        assert_eq!(connections[0].node, "aaa");
+        assert_eq!(connections[0].neighbors, vec!["you", "hhh"]);
+    }
+
+    #[test]
+    fn test_generate() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let input = generate(100, &mut rng);
+        let (_, connections) = parse_input(&input).unwrap();
+        assert_eq!(connections.len(), 104);
+        let graph = build_graph(&connections);
+        assert!(graph.find_cycle().is_none());
+        assert!(num_paths(&graph, "you", "out").unwrap() > 0);
+        let output = Day11.part2(&input).unwrap();
+        assert!(!output.ends_with(": 0"), "{}", output);
+    }
+
+    #[test]
+    fn test_part2_overflow() {
+        // 2^33 paths from svr to dac and from dac to fft
+        let mut input = String::new();
+        for (from, to) in [("svr", "dac"), ("dac", "fft")] {
+            for i in 0..33 {
+                let node = |n: usize| match n {
+                    0 => from.to_string(),
+                    33 => to.to_string(),
+                    n => format!("{}{}", from, n),
+                };
+                input.push_str(&format!("{}: {}a{} {}b{}\n", node(i), from, i, from, i));
+                input.push_str(&format!("{}a{}: {}\n", from, i, node(i + 1)));
+                input.push_str(&format!("{}b{}: {}\n", from, i, node(i + 1)));
+            }
+        }
+        input.push_str("fft: out\n");
+        let err = Day11.part2(&input).unwrap_err();
+        assert_eq!(err.to_string(), GraphError::Overflow.to_string());
+    }
+
+    #[test]
+    fn test_part1_sample() {
+        let result = Day11.part1(SAMPLE_INPUT_1).unwrap();
+        assert_eq!(result, "Number of distinct paths from 'you' to 'out': 5");
+    }
+
+    #[test]
+    fn test_part2_sample() {
+        let result = Day11.part2(SAMPLE_INPUT_2).unwrap();
+        assert_eq!(result, "Paths going through 'dac' and 'fft': 2");
+    }
+}
@@ -6,6 +6,8 @@ use nom::{
     multi::{count, separated_list1},
     sequence::{separated_pair, terminated},
 };
+use rand::{Rng, rngs::StdRng};
+use tracing::instrument;
 
 pub struct Day12;
 
@@ -94,6 +96,7 @@ fn parse_entries(input: &str) -> IResult<&str, Vec<DimensionEntry>> {
 }
 
 // Parse the complete input
+#[instrument(name = "parse", level = "debug", skip_all)]
 fn parse_input(input: &str) -> Result<Input, String> {
     let (input, figures) =
         parse_figures(input).map_err(|e| format!("Failed to parse figures: {:?}", e))?;
@@ -106,6 +109,57 @@ fn parse_input(input: &str) -> Result<Input, String> {
     Ok(Input { figures, entries })
 }
 
+/// Entry point for the `parse_day12` fuzz target.
+#[cfg(fuzzing)]
+pub fn fuzz_parse(input: &str) {
+    let _ = parse_input(input);
+}
+
+/// Generates six random figures and `size` regions. Each region either has
+/// a 3x3 slot for every present or more present area than it has cells, so
+/// part 1 never hits the inconclusive case.
+pub fn generate(size: usize, rng: &mut StdRng) -> String {
+    let mut out = String::new();
+    let mut figure_sizes = [0u32; 6];
+    for (id, figure_size) in figure_sizes.iter_mut().enumerate() {
+        out.push_str(&format!("{}:\n", id));
+        for row in 0..3 {
+            for col in 0..3 {
+                if (row, col) == (1, 1) || rng.random_bool(0.7) {
+                    out.push('#');
+                    *figure_size += 1;
+                } else {
+                    out.push('.');
+                }
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+
+    for _ in 0..size {
+        let width = rng.random_range(12..=50);
+        let height = rng.random_range(12..=50);
+        let mut numbers = [0u32; 6];
+        if rng.random_bool(0.5) {
+            let slots = (width / 3) * (height / 3);
+            for _ in 0..rng.random_range(slots / 2..=slots) {
+                numbers[rng.random_range(0..6)] += 1;
+            }
+        } else {
+            let mut area = 0;
+            while area <= width * height {
+                let figure = rng.random_range(0..6);
+                numbers[figure] += 1;
+                area += figure_sizes[figure];
+            }
+        }
+        let numbers: Vec<String> = numbers.iter().map(|n| n.to_string()).collect();
+        out.push_str(&format!("{}x{}: {}\n", width, height, numbers.join(" ")));
+    }
+    out
+}
+
 enum FitOrNot {
     Fits,
     DoesNotFit,
@@ -134,7 +188,7 @@ fn decide(entry: &DimensionEntry, figure_sizes: &[u32]) -> FitOrNot {
 
 impl Solution for Day12 {
     fn part1(&self, input: &str) -> anyhow::Result<String> {
-        let parsed = parse_input(input).unwrap();
+        let parsed = parse_input(input).map_err(anyhow::Error::msg)?;
         let figure_sizes = {
             let mut sizes = Vec::new();
             for fig in &parsed.figures {
@@ -178,6 +232,153 @@ impl Solution for Day12 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
+
+    type Pattern = [[bool; 3]; 3];
+
+    // The distinct rotations and reflections of a pattern
+    fn orientations(pattern: &Pattern) -> Vec<Pattern> {
+        let rotate = |p: &Pattern| {
+            let mut out = [[false; 3]; 3];
+            for (r, row) in p.iter().enumerate() {
+                for (c, &cell) in row.iter().enumerate() {
+                    out[c][2 - r] = cell;
+                }
+            }
+            out
+        };
+        let mut all = Vec::new();
+        for mut p in [*pattern, pattern.map(|row| [row[2], row[1], row[0]])] {
+            for _ in 0..4 {
+                if !all.contains(&p) {
+                    all.push(p);
+                }
+                p = rotate(&p);
+            }
+        }
+        all
+    }
+
+    // Reference for `decide`: backtracking over every placement of every
+    // present, for regions small enough to search exhaustively
+    fn naive_fits(entry: &DimensionEntry, figures: &[Figure]) -> bool {
+        let (width, height) = (entry.width as usize, entry.height as usize);
+        let pieces: Vec<Vec<Pattern>> = entry
+            .numbers
+            .iter()
+            .zip(figures)
+            .flat_map(|(&n, f)| (0..n).map(|_| orientations(&f.pattern)))
+            .collect();
+        let area: usize = pieces
+            .iter()
+            .map(|p| p[0].as_flattened().iter().filter(|&&b| b).count())
+            .sum();
+        if area > width * height {
+            return false;
+        }
+
+        fn place(pieces: &[Vec<Pattern>], grid: &mut Vec<Vec<bool>>) -> bool {
+            let Some((piece, rest)) = pieces.split_first() else {
+                return true;
+            };
+            let (height, width) = (grid.len(), grid[0].len());
+            for pattern in piece {
+                for r in 0..height.saturating_sub(2) {
+                    for c in 0..width.saturating_sub(2) {
+                        let cells: Vec<(usize, usize)> = (0..3)
+                            .flat_map(|dr| (0..3).map(move |dc| (dr, dc)))
+                            .filter(|&(dr, dc)| pattern[dr][dc])
+                            .map(|(dr, dc)| (r + dr, c + dc))
+                            .collect();
+                        if cells.iter().any(|&(r, c)| grid[r][c]) {
+                            continue;
+                        }
+                        cells.iter().for_each(|&(r, c)| grid[r][c] = true);
+                        if place(rest, grid) {
+                            return true;
+                        }
+                        cells.iter().for_each(|&(r, c)| grid[r][c] = false);
+                    }
+                }
+            }
+            false
+        }
+        place(&pieces, &mut vec![vec![false; width]; height])
+    }
+
+    fn figures() -> impl Strategy<Value = Vec<Figure>> {
+        let pattern = any::<[[bool; 3]; 3]>().prop_map(|mut pattern| {
+            pattern[1][1] = true;
+            Figure { pattern }
+        });
+        prop::collection::vec(pattern, 6)
+    }
+
+    fn entries() -> impl Strategy<Value = DimensionEntry> {
+        (3u32..=6, 3u32..=6, prop::collection::vec(0usize..6, 0..=4)).prop_map(
+            |(width, height, presents)| {
+                let mut numbers = [0; 6];
+                for figure in presents {
+                    numbers[figure] += 1;
+                }
+                DimensionEntry {
+                    width,
+                    height,
+                    numbers,
+                }
+            },
+        )
+    }
+
+    #[test]
+    fn test_naive_fits() {
+        let full = Figure {
+            pattern: [[true; 3]; 3],
+        };
+        let corner = Figure {
+            pattern: [
+                [true, true, true],
+                [true, true, false],
+                [true, false, false],
+            ],
+        };
+        assert_eq!(orientations(&full.pattern).len(), 1);
+        assert_eq!(orientations(&corner.pattern).len(), 4);
+        let entry = |width, height, numbers| DimensionEntry {
+            width,
+            height,
+            numbers,
+        };
+        let figures = vec![
+            full,
+            corner.clone(),
+            corner.clone(),
+            corner.clone(),
+            corner.clone(),
+            corner,
+        ];
+        assert!(naive_fits(&entry(3, 6, [2, 0, 0, 0, 0, 0]), &figures));
+        assert!(!naive_fits(&entry(5, 3, [2, 0, 0, 0, 0, 0]), &figures));
+        // Two corners interlock in a 4 x 3 region, which has one 3 x 3 slot
+        assert!(naive_fits(&entry(4, 3, [0, 2, 0, 0, 0, 0]), &figures));
+        assert!(!naive_fits(&entry(4, 3, [1, 1, 0, 0, 0, 0]), &figures));
+    }
+
+    proptest! {
+        #[test]
+        fn test_decide_matches_reference(figures in figures(), entry in entries()) {
+            let sizes: Vec<u32> = figures
+                .iter()
+                .map(|f| f.pattern.as_flattened().iter().filter(|&&b| b).count() as u32)
+                .collect();
+            match decide(&entry, &sizes) {
+                FitOrNot::Fits => prop_assert!(naive_fits(&entry, &figures)),
+                FitOrNot::DoesNotFit => prop_assert!(!naive_fits(&entry, &figures)),
+                FitOrNot::Inconclusive => {}
+            }
+        }
+    }
 
     #[test]
     fn test_parse_figure() {
@@ -190,6 +391,15 @@ mod tests {
         assert_eq!(figure.pattern[2], [true, false, true]);
     }
 
+    #[test]
+    fn test_generate() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let parsed = parse_input(&generate(30, &mut rng)).unwrap();
+        assert_eq!(parsed.entries.len(), 30);
+        let output = Day12.part1(&generate(30, &mut rng)).unwrap();
+        assert!(!output.contains("Inconclusive"), "{}", output);
+    }
+
     #[test]
     fn test_parse_dimension_entry() {
         let input = "39x43: 23 41 27 30 29 31";
@@ -1,4 +1,5 @@
 use crate::Solution;
+use rand::rngs::StdRng;
 
 pub mod day01;
 pub mod day02;
@@ -16,9 +17,9 @@ pub mod day12;
 pub fn get_solution(day: u8) -> Option<Box<dyn Solution>> {
     match day {
         1 => Some(Box::new(day01::Day01)),
-        2 => Some(Box::new(day02::Day02)),
-        3 => Some(Box::new(day03::Day03)),
-        4 => Some(Box::new(day04::Day04)),
+        2 => Some(Box::new(day02::Day02::default())),
+        3 => Some(Box::new(day03::Day03::default())),
+        4 => Some(Box::new(day04::Day04::default())),
         5 => Some(Box::new(day05::Day05)),
         6 => Some(Box::new(day06::Day06)),
         7 => Some(Box::new(day07::Day07)),
@@ -30,3 +31,25 @@ pub fn get_solution(day: u8) -> Option<Box<dyn Solution>> {
         _ => None,
     }
 }
+
+/// Random input generator for a day: takes a size and a seeded RNG.
+/// Most days cannot parse an input of size 0, so sizes start at 1.
+pub type Generator = fn(usize, &mut StdRng) -> String;
+
+pub fn get_generator(day: u8) -> Option<Generator> {
+    match day {
+        1 => Some(day01::generate),
+        2 => Some(day02::generate),
+        3 => Some(day03::generate),
+        4 => Some(day04::generate),
+        5 => Some(day05::generate),
+        6 => Some(day06::generate),
+        7 => Some(day07::generate),
+        8 => Some(day08::generate),
+        9 => Some(day09::generate),
+        10 => Some(day10::generate),
+        11 => Some(day11::generate),
+        12 => Some(day12::generate),
+        _ => None,
+    }
+}
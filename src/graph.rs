@@ -0,0 +1,358 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// Index of a node inside a [`Graph`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    /// The nodes form a cycle, listed in edge order.
+    Cycle(Vec<NodeId>),
+    /// The number of paths does not fit in a `u64`.
+    Overflow,
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::Cycle(nodes) => {
+                write!(f, "graph contains a cycle of {} nodes", nodes.len())
+            }
+            GraphError::Overflow => write!(f, "number of paths overflows u64"),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+/// Directed graph whose nodes are identified by interned string names.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    names: Vec<String>,
+    ids: HashMap<String, NodeId>,
+    edges: Vec<Vec<NodeId>>,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the id of the node called `name`, creating it if needed.
+    pub fn add_node(&mut self, name: &str) -> NodeId {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = NodeId(self.names.len());
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.edges.push(Vec::new());
+        id
+    }
+
+    pub fn add_edge(&mut self, from: &str, to: &str) {
+        let from = self.add_node(from);
+        let to = self.add_node(to);
+        self.edges[from.0].push(to);
+    }
+
+    pub fn node(&self, name: &str) -> Option<NodeId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: NodeId) -> &str {
+        &self.names[id.0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = NodeId> + '_ {
+        (0..self.names.len()).map(NodeId)
+    }
+
+    pub fn neighbors(&self, id: NodeId) -> &[NodeId] {
+        &self.edges[id.0]
+    }
+
+    pub fn num_edges(&self) -> usize {
+        self.edges.iter().map(|e| e.len()).sum()
+    }
+
+    /// Topological order of all nodes (Kahn's algorithm).
+    /// Fails with the nodes of one cycle if the graph is not a DAG.
+    pub fn topological_order(&self) -> Result<Vec<NodeId>, GraphError> {
+        self.topological_order_of(&vec![true; self.len()])
+    }
+
+    /// Returns some cycle of the graph, or `None` if it is acyclic.
+    pub fn find_cycle(&self) -> Option<Vec<NodeId>> {
+        match self.topological_order() {
+            Ok(_) => None,
+            Err(GraphError::Cycle(cycle)) => Some(cycle),
+            Err(GraphError::Overflow) => unreachable!(),
+        }
+    }
+
+    /// Marks every node reachable from `from` (including `from` itself).
+    pub fn reachable_from(&self, from: NodeId) -> Vec<bool> {
+        mark_reachable(&self.edges, from)
+    }
+
+    /// Marks every node from which `to` can be reached (including `to` itself).
+    pub fn reaching(&self, to: NodeId) -> Vec<bool> {
+        mark_reachable(&self.reversed_edges(), to)
+    }
+
+    pub fn is_reachable(&self, from: NodeId, to: NodeId) -> bool {
+        self.reachable_from(from)[to.0]
+    }
+
+    /// Number of distinct paths from `from` to `to`.
+    ///
+    /// Only the nodes lying on some `from -> to` path are considered, so cycles
+    /// elsewhere in the graph are fine. A cycle on such a path means there are
+    /// infinitely many paths and is reported as an error.
+    pub fn count_paths(&self, from: NodeId, to: NodeId) -> Result<u64, GraphError> {
+        let forward = self.reachable_from(from);
+        let backward = self.reaching(to);
+        let relevant: Vec<bool> = forward
+            .iter()
+            .zip(&backward)
+            .map(|(&a, &b)| a && b)
+            .collect();
+        if !relevant[from.0] {
+            return Ok(0);
+        }
+
+        let order = self.topological_order_of(&relevant)?;
+        let mut paths = vec![0u64; self.len()];
+        paths[to.0] = 1;
+        for &node in order.iter().rev() {
+            if node == to {
+                continue;
+            }
+            let mut total = 0u64;
+            for &next in self.neighbors(node) {
+                if relevant[next.0] {
+                    total = total
+                        .checked_add(paths[next.0])
+                        .ok_or(GraphError::Overflow)?;
+                }
+            }
+            paths[node.0] = total;
+        }
+        Ok(paths[from.0])
+    }
+
+    /// Renders the graph in Graphviz DOT format.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph {\n");
+        for node in self.nodes() {
+            out.push_str(&format!("    {:?};\n", self.name(node)));
+        }
+        for node in self.nodes() {
+            for &next in self.neighbors(node) {
+                out.push_str(&format!(
+                    "    {:?} -> {:?};\n",
+                    self.name(node),
+                    self.name(next)
+                ));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    // Adjacency lists with every edge pointing the other way
+    fn reversed_edges(&self) -> Vec<Vec<NodeId>> {
+        let mut edges = vec![Vec::new(); self.len()];
+        for node in self.nodes() {
+            for &next in self.neighbors(node) {
+                edges[next.0].push(node);
+            }
+        }
+        edges
+    }
+
+    // Kahn's algorithm restricted to the nodes where `include` is set
+    fn topological_order_of(&self, include: &[bool]) -> Result<Vec<NodeId>, GraphError> {
+        let mut in_degree = vec![0usize; self.len()];
+        for node in self.nodes().filter(|n| include[n.0]) {
+            for &next in self.neighbors(node) {
+                if include[next.0] {
+                    in_degree[next.0] += 1;
+                }
+            }
+        }
+
+        let mut queue: VecDeque<NodeId> = self
+            .nodes()
+            .filter(|n| include[n.0] && in_degree[n.0] == 0)
+            .collect();
+        let mut order = Vec::new();
+        while let Some(node) = queue.pop_front() {
+            order.push(node);
+            for &next in self.neighbors(node) {
+                if include[next.0] {
+                    in_degree[next.0] -= 1;
+                    if in_degree[next.0] == 0 {
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        let remaining = include.iter().filter(|&&b| b).count();
+        if order.len() == remaining {
+            Ok(order)
+        } else {
+            Err(GraphError::Cycle(self.cycle_among(&in_degree, include)))
+        }
+    }
+
+    // Every node left with a positive in-degree after Kahn's algorithm has a
+    // predecessor that is also left over, so walking predecessors must loop.
+    fn cycle_among(&self, in_degree: &[usize], include: &[bool]) -> Vec<NodeId> {
+        let leftover = |n: NodeId| include[n.0] && in_degree[n.0] > 0;
+        let mut predecessor = vec![None; self.len()];
+        for node in self.nodes().filter(|&n| leftover(n)) {
+            for &next in self.neighbors(node) {
+                if leftover(next) {
+                    predecessor[next.0] = Some(node);
+                }
+            }
+        }
+
+        let start = self.nodes().find(|&n| leftover(n)).unwrap();
+        let mut position = vec![None; self.len()];
+        let mut walk = Vec::new();
+        let mut node = start;
+        while position[node.0].is_none() {
+            position[node.0] = Some(walk.len());
+            walk.push(node);
+            node = predecessor[node.0].unwrap();
+        }
+        let mut cycle = walk.split_off(position[node.0].unwrap());
+        cycle.reverse();
+        cycle
+    }
+}
+
+// Depth-first search over adjacency lists, marking the nodes seen from `from`
+fn mark_reachable(edges: &[Vec<NodeId>], from: NodeId) -> Vec<bool> {
+    let mut seen = vec![false; edges.len()];
+    let mut stack = vec![from];
+    seen[from.0] = true;
+    while let Some(node) = stack.pop() {
+        for &next in &edges[node.0] {
+            if !seen[next.0] {
+                seen[next.0] = true;
+                stack.push(next);
+            }
+        }
+    }
+    seen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(&str, &str)]) -> Graph {
+        let mut g = Graph::new();
+        for (a, b) in edges {
+            g.add_edge(a, b);
+        }
+        g
+    }
+
+    #[test]
+    fn test_interning() {
+        let mut g = Graph::new();
+        let a = g.add_node("a");
+        let b = g.add_node("b");
+        assert_eq!(g.add_node("a"), a);
+        assert_ne!(a, b);
+        assert_eq!(g.name(b), "b");
+        assert_eq!(g.node("c"), None);
+        assert_eq!(g.len(), 2);
+    }
+
+    #[test]
+    fn test_topological_order() {
+        let g = graph(&[("a", "b"), ("a", "c"), ("b", "d"), ("c", "d")]);
+        let order = g.topological_order().unwrap();
+        let pos = |name| order.iter().position(|&n| g.name(n) == name).unwrap();
+        assert!(pos("a") < pos("b"));
+        assert!(pos("a") < pos("c"));
+        assert!(pos("b") < pos("d"));
+        assert!(pos("c") < pos("d"));
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let g = graph(&[("s", "a"), ("a", "b"), ("b", "c"), ("c", "a"), ("c", "t")]);
+        let cycle = g.find_cycle().unwrap();
+        let mut names: Vec<&str> = cycle.iter().map(|&n| g.name(n)).collect();
+        for i in 0..cycle.len() {
+            let next = cycle[(i + 1) % cycle.len()];
+            assert!(g.neighbors(cycle[i]).contains(&next));
+        }
+        names.sort();
+        assert_eq!(names, vec!["a", "b", "c"]);
+
+        assert!(graph(&[("a", "b")]).find_cycle().is_none());
+    }
+
+    #[test]
+    fn test_count_paths() {
+        let g = graph(&[("a", "b"), ("a", "c"), ("b", "d"), ("c", "d"), ("d", "e")]);
+        let id = |name| g.node(name).unwrap();
+        assert_eq!(g.count_paths(id("a"), id("e")), Ok(2));
+        assert_eq!(g.count_paths(id("a"), id("a")), Ok(1));
+        assert_eq!(g.count_paths(id("e"), id("a")), Ok(0));
+    }
+
+    #[test]
+    fn test_count_paths_ignores_unrelated_cycles() {
+        let g = graph(&[("a", "b"), ("b", "x"), ("x", "y"), ("y", "x"), ("a", "c")]);
+        let id = |name| g.node(name).unwrap();
+        assert_eq!(g.count_paths(id("a"), id("c")), Ok(1));
+        assert!(matches!(
+            g.count_paths(id("a"), id("y")),
+            Err(GraphError::Cycle(_))
+        ));
+    }
+
+    #[test]
+    fn test_reachability() {
+        let g = graph(&[("a", "b"), ("b", "c"), ("d", "c")]);
+        let id = |name| g.node(name).unwrap();
+        assert!(g.is_reachable(id("a"), id("c")));
+        assert!(!g.is_reachable(id("a"), id("d")));
+        assert_eq!(g.reaching(id("c")), vec![true, true, true, true]);
+        assert_eq!(g.reaching(id("b")), vec![true, true, false, false]);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let g = graph(&[("a", "b")]);
+        assert_eq!(
+            g.to_dot(),
+            "digraph {\n    \"a\";\n    \"b\";\n    \"a\" -> \"b\";\n}\n"
+        );
+    }
+}
@@ -1,6 +1,25 @@
+use std::io::BufRead;
+
 pub mod days;
+pub mod graph;
 
 pub trait Solution {
     fn part1(&self, input: &str) -> anyhow::Result<String>;
     fn part2(&self, input: &str) -> anyhow::Result<String>;
+
+    /// Solves part 1 from a reader. Reads it whole unless the day can stream.
+    fn part1_from(&self, reader: &mut dyn BufRead) -> anyhow::Result<String> {
+        self.part1(&read_all(reader)?)
+    }
+
+    /// Solves part 2 from a reader. Reads it whole unless the day can stream.
+    fn part2_from(&self, reader: &mut dyn BufRead) -> anyhow::Result<String> {
+        self.part2(&read_all(reader)?)
+    }
+}
+
+fn read_all(reader: &mut dyn BufRead) -> anyhow::Result<String> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    Ok(input)
 }
@@ -1,18 +1,28 @@
 use anyhow::bail;
-use aoc2025::days;
-use clap::Parser;
+use aoc2025::Solution;
+use aoc2025::days::{self, day01, day02, day03, day04};
+use clap::{ArgAction, Parser, Subcommand};
+use rand::{SeedableRng, rngs::StdRng};
 use std::fs;
+use std::io::{self, IsTerminal};
 use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{info, info_span, level_filters::LevelFilter};
+use tracing_subscriber::fmt::format::FmtSpan;
 
 #[derive(Parser)]
 #[command(name = "aoc2025")]
 #[command(about = "Advent of Code 2025 Solutions", long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    #[arg(help = "Day number (1-25)")]
-    day: u8,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    #[arg(help = "Part number (1 or 2)")]
-    part: u8,
+    #[arg(help = "Day number (1-25)", required = true)]
+    day: Option<u8>,
+
+    #[arg(help = "Part number (1 or 2)", required = true)]
+    part: Option<u8>,
 
     #[arg(
         short,
@@ -20,6 +30,331 @@ struct Args {
         help = "Path to input file (defaults to inputs/dayXX.txt)"
     )]
     input: Option<PathBuf>,
+
+    #[arg(
+        short,
+        long,
+        action = ArgAction::Count,
+        global = true,
+        help = "Log more to stderr (-v info, -vv debug, -vvv trace)"
+    )]
+    verbose: u8,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        conflicts_with = "verbose",
+        help = "Only log errors"
+    )]
+    quiet: bool,
+
+    #[command(flatten)]
+    options: DayOptions,
+}
+
+/// Options that only one day reads. Giving them for another day is an error
+/// rather than being silently ignored.
+#[derive(clap::Args)]
+struct DayOptions {
+    #[command(flatten)]
+    day02: Day02Options,
+
+    #[command(flatten)]
+    day03: Day03Options,
+
+    #[command(flatten)]
+    day04: Day04Options,
+}
+
+// Command-line options of a single day
+trait DayArgs {
+    fn day(&self) -> u8;
+
+    // Names of the flags that were given
+    fn given(&self) -> Vec<&'static str>;
+
+    fn solution(&self) -> anyhow::Result<Box<dyn Solution>>;
+}
+
+impl DayOptions {
+    fn all(&self) -> [&dyn DayArgs; 3] {
+        [&self.day02, &self.day03, &self.day04]
+    }
+
+    fn check(&self, day: u8) -> anyhow::Result<()> {
+        for options in self.all().iter().filter(|options| options.day() != day) {
+            if let Some(flag) = options.given().first() {
+                bail!("Error: --{} only applies to day {}", flag, options.day());
+            }
+        }
+        Ok(())
+    }
+
+    fn solution(&self, day: u8) -> anyhow::Result<Box<dyn Solution>> {
+        self.check(day)?;
+        match self.all().iter().find(|options| options.day() == day) {
+            Some(options) => options.solution(),
+            None => days::get_solution(day)
+                .ok_or(anyhow::anyhow!("Error: Day {} is not implemented", day)),
+        }
+    }
+
+    fn render(&self, day: u8, input: &str, part: u8, color: bool) -> anyhow::Result<String> {
+        self.check(day)?;
+        match day {
+            3 => self.day03.config().render(input, part, color),
+            4 => self.day04.config().render(input, part, color),
+            _ => bail!("Error: Day {} has no rendering", day),
+        }
+    }
+}
+
+#[derive(clap::Args)]
+#[command(next_help_heading = "Day02 options")]
+struct Day02Options {
+    #[arg(long, help = "Base the IDs are written in (2-36) [default: 10]")]
+    base: Option<u32>,
+
+    #[arg(
+        long,
+        help = "Swap reversed ranges and merge overlapping ones instead of failing"
+    )]
+    normalize: bool,
+}
+
+impl DayArgs for Day02Options {
+    fn day(&self) -> u8 {
+        2
+    }
+
+    fn given(&self) -> Vec<&'static str> {
+        [("base", self.base.is_some()), ("normalize", self.normalize)]
+            .into_iter()
+            .filter_map(|(flag, given)| given.then_some(flag))
+            .collect()
+    }
+
+    fn solution(&self) -> anyhow::Result<Box<dyn Solution>> {
+        let base = match self.base {
+            Some(base) => day02::Base::new(base)?,
+            None => day02::Base::default(),
+        };
+        Ok(Box::new(day02::Day02 {
+            base,
+            normalize: self.normalize,
+        }))
+    }
+}
+
+#[derive(clap::Args)]
+#[command(next_help_heading = "Day03 options")]
+struct Day03Options {
+    #[arg(
+        long,
+        help = "Number of batteries to turn on per bank [default: 2 or 12]"
+    )]
+    digits: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Leave out banks with too few batteries instead of failing"
+    )]
+    skip_short_banks: bool,
+}
+
+impl DayArgs for Day03Options {
+    fn day(&self) -> u8 {
+        3
+    }
+
+    fn given(&self) -> Vec<&'static str> {
+        [
+            ("digits", self.digits.is_some()),
+            ("skip-short-banks", self.skip_short_banks),
+        ]
+        .into_iter()
+        .filter_map(|(flag, given)| given.then_some(flag))
+        .collect()
+    }
+
+    fn solution(&self) -> anyhow::Result<Box<dyn Solution>> {
+        Ok(Box::new(self.config()))
+    }
+}
+
+impl Day03Options {
+    fn config(&self) -> day03::Day03 {
+        day03::Day03 {
+            digits: self.digits,
+            short_banks: if self.skip_short_banks {
+                day03::ShortBanks::Skip
+            } else {
+                day03::ShortBanks::Error
+            },
+        }
+    }
+}
+
+#[derive(clap::Args)]
+#[command(next_help_heading = "Day04 options")]
+struct Day04Options {
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        help = "Cells that count as neighbours: moore, von-neumann or \"row,col;...\" offsets [default: moore]"
+    )]
+    neighborhood: Option<day04::Neighborhood>,
+
+    #[arg(
+        long,
+        help = "Number of neighbouring rolls to compare with [default: 4]"
+    )]
+    threshold: Option<usize>,
+
+    #[arg(
+        long,
+        help = "How the neighbouring rolls must compare to the threshold: < <= = != >= > [default: <]"
+    )]
+    compare: Option<day04::Comparison>,
+
+    #[arg(
+        long,
+        help = "What lies past the edges of the grid: empty, rolls or torus [default: empty]"
+    )]
+    edges: Option<day04::Edges>,
+}
+
+impl DayArgs for Day04Options {
+    fn day(&self) -> u8 {
+        4
+    }
+
+    fn given(&self) -> Vec<&'static str> {
+        [
+            ("neighborhood", self.neighborhood.is_some()),
+            ("threshold", self.threshold.is_some()),
+            ("compare", self.compare.is_some()),
+            ("edges", self.edges.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(flag, given)| given.then_some(flag))
+        .collect()
+    }
+
+    fn solution(&self) -> anyhow::Result<Box<dyn Solution>> {
+        Ok(Box::new(self.config()))
+    }
+}
+
+impl Day04Options {
+    fn config(&self) -> day04::Day04 {
+        let default = day04::Rule::default();
+        day04::Day04 {
+            rule: day04::Rule {
+                neighborhood: self.neighborhood.clone().unwrap_or(default.neighborhood),
+                threshold: self.threshold.unwrap_or(default.threshold),
+                comparison: self.compare.unwrap_or(default.comparison),
+                edges: self.edges.unwrap_or(default.edges),
+            },
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print a random input for a day to stdout
+    Gen {
+        #[arg(help = "Day number (1-25)")]
+        day: u8,
+
+        #[arg(
+            short,
+            long,
+            default_value_t = 100,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+            help = "Size of the input (at least 1)"
+        )]
+        size: usize,
+
+        #[arg(long, default_value_t = 0, help = "Random seed")]
+        seed: u64,
+    },
+    /// Print the dial position after every Day01 instruction
+    Trace {
+        #[arg(
+            short,
+            long,
+            help = "Path to input file (defaults to inputs/day01.txt)"
+        )]
+        input: Option<PathBuf>,
+
+        #[arg(long, help = "Print CSV instead of a table")]
+        csv: bool,
+
+        #[arg(long, default_value_t = 100, help = "Number of dial positions")]
+        modulus: u64,
+
+        #[arg(long, default_value_t = 50, help = "Start position")]
+        start: u64,
+
+        #[arg(
+            long = "target",
+            default_values_t = [0],
+            help = "Target position, can be repeated"
+        )]
+        targets: Vec<u64>,
+    },
+    /// Print a day's input with the parts of the answer highlighted
+    Render {
+        #[arg(help = "Day number (1-25)")]
+        day: u8,
+
+        #[arg(help = "Part number (1 or 2)", value_parser = clap::value_parser!(u8).range(1..=2))]
+        part: u8,
+
+        #[arg(
+            short,
+            long,
+            help = "Path to input file (defaults to inputs/dayXX.txt)"
+        )]
+        input: Option<PathBuf>,
+
+        #[arg(long, help = "Mark with plain characters instead of colors")]
+        plain: bool,
+
+        #[command(flatten)]
+        options: DayOptions,
+    },
+    /// Play the Day04 removal rounds in the terminal or save them as images
+    Animate {
+        #[arg(
+            short,
+            long,
+            help = "Path to input file (defaults to inputs/day04.txt)"
+        )]
+        input: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "DIR",
+            help = "Write one PPM image per round to this directory instead"
+        )]
+        ppm: Option<PathBuf>,
+
+        #[arg(long, default_value_t = 4, help = "Pixels per cell in the images")]
+        scale: usize,
+
+        #[arg(
+            long,
+            default_value_t = 300,
+            help = "Milliseconds between rounds in the terminal"
+        )]
+        delay: u64,
+
+        #[command(flatten)]
+        options: Day04Options,
+    },
 }
 
 enum Part {
@@ -27,10 +362,100 @@ enum Part {
     Two,
 }
 
+// Logs go to stderr so they never mix with the answers on stdout
+fn init_logging(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::ERROR,
+        (false, 0) => LevelFilter::WARN,
+        (false, 1) => LevelFilter::INFO,
+        (false, 2) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    };
+    // Closing a span logs how long it took
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(level)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_target(false)
+        .init();
+}
+
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    init_logging(args.verbose, args.quiet);
 
-    let part = match args.part {
+    match args.command {
+        Some(Command::Gen { day, size, seed }) => {
+            let generate = days::get_generator(day)
+                .ok_or(anyhow::anyhow!("Error: Day {} has no input generator", day))?;
+            let mut rng = StdRng::seed_from_u64(seed);
+            print!("{}", generate(size, &mut rng));
+            return Ok(());
+        }
+        Some(Command::Trace {
+            input,
+            csv,
+            modulus,
+            start,
+            targets,
+        }) => {
+            let dial = day01::Dial::new(modulus, start, &targets)?;
+            let input_path = input.unwrap_or_else(|| PathBuf::from("inputs/day01.txt"));
+            let reader = io::BufReader::new(fs::File::open(&input_path)?);
+            let format = if csv {
+                day01::TraceFormat::Csv
+            } else {
+                day01::TraceFormat::Table
+            };
+            day01::with_instructions(reader, |instructions| {
+                let steps = day01::trace(dial, instructions);
+                day01::write_trace(&mut io::stdout().lock(), steps, format)
+            })??;
+            return Ok(());
+        }
+        Some(Command::Render {
+            day,
+            part,
+            input,
+            plain,
+            options,
+        }) => {
+            let input_path =
+                input.unwrap_or_else(|| PathBuf::from(format!("inputs/day{:02}.txt", day)));
+            let input = fs::read_to_string(&input_path)?;
+            let color = !plain && io::stdout().is_terminal();
+            print!("{}", options.render(day, &input, part, color)?);
+            return Ok(());
+        }
+        Some(Command::Animate {
+            input,
+            ppm,
+            scale,
+            delay,
+            options,
+        }) => {
+            let input_path = input.unwrap_or_else(|| PathBuf::from("inputs/day04.txt"));
+            let input = fs::read_to_string(&input_path)?;
+            let frames = options.config().frames(&input)?;
+            match ppm {
+                Some(dir) => {
+                    day04::export_ppm_frames(&dir, &frames, scale)?;
+                    info!("Wrote {} frames to {}", frames.len(), dir.display());
+                }
+                None => day04::write_animation(
+                    &mut io::stdout().lock(),
+                    &frames,
+                    Duration::from_millis(delay),
+                )?,
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
+    // Both are required by clap unless a subcommand is given
+    let day = args.day.unwrap();
+    let part = match args.part.unwrap() {
         1 => Part::One,
         2 => Part::Two,
         _ => {
@@ -38,21 +463,21 @@ fn main() -> anyhow::Result<()> {
         }
     };
 
-    let solution = days::get_solution(args.day).ok_or(anyhow::anyhow!(
-        "Error: Day {} is not implemented",
-        args.day
-    ))?;
+    let solution = args.options.solution(day)?;
 
     let input_path = args
         .input
-        .unwrap_or_else(|| PathBuf::from(format!("inputs/day{:02}.txt", args.day)));
+        .unwrap_or_else(|| PathBuf::from(format!("inputs/day{:02}.txt", day)));
 
-    let input = fs::read_to_string(&input_path)?;
+    // Days that can stream their input never hold all of it in memory
+    let mut reader = io::BufReader::new(fs::File::open(&input_path)?);
+    info!("Reading {}", input_path.display());
 
-    let result = match part {
-        Part::One => solution.part1(&input),
-        Part::Two => solution.part2(&input),
-    };
+    let span = info_span!("solve", day, part = args.part.unwrap());
+    let result = span.in_scope(|| match part {
+        Part::One => solution.part1_from(&mut reader),
+        Part::Two => solution.part2_from(&mut reader),
+    });
     match result {
         Ok(res) => println!("{}", res),
         Err(e) => {